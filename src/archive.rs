@@ -0,0 +1,113 @@
+use std::cmp::Ordering;
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use tracing::{info, instrument};
+
+use crate::{
+    models::PackageData,
+    rpc::{into_packages, link_split_packages, RpcPackage, AUR_URL},
+};
+
+/// Daily dump of the metadata of all AUR packages, in the format of the RPC `info` results
+pub static AUR_META_ARCHIVE_URL: &str = "https://aur.archlinux.org/packages-meta-ext-v1.json.gz";
+
+/// First bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Reads packages from the metadata archive stored in the local file or served under the URL.
+/// Packages are ordered by popularity like on the packages list and have no comments.
+#[instrument]
+pub async fn read_meta_archive(location: &str) -> Result<Vec<PackageData>> {
+    let bytes = if location.starts_with("http://") || location.starts_with("https://") {
+        reqwest::get(location)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec()
+    } else {
+        tokio::fs::read(location)
+            .await
+            .with_context(|| format!("Cannot read archive {}", location))?
+    };
+
+    info!("Read {} bytes of the metadata archive", bytes.len());
+
+    parse_meta_archive(&bytes)
+}
+
+/// Parses the gzipped archive, plain JSON is accepted as well, as HTTP clients may decompress
+/// the archive on the fly.
+fn parse_meta_archive(bytes: &[u8]) -> Result<Vec<PackageData>> {
+    let results: Vec<RpcPackage> = if bytes.starts_with(&GZIP_MAGIC) {
+        serde_json::from_reader(GzDecoder::new(bytes))
+    } else {
+        serde_json::from_slice(bytes)
+    }
+    .context("Cannot parse metadata archive")?;
+
+    let mut packages = into_packages(results, AUR_URL);
+
+    // The archive holds every package, so all split packages get linked
+    link_split_packages(&mut packages);
+    packages.sort_by(|a, b| {
+        b.basic
+            .popularity
+            .partial_cmp(&a.basic.popularity)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::parse_meta_archive;
+    use anyhow::Result;
+    use flate2::{write::GzEncoder, Compression};
+
+    #[test]
+    fn parse_gzipped_archive_and_link_split_packages() -> Result<()> {
+        // Arrange
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(include_bytes!("../tests/fixtures/packages_meta_ext.json"))?;
+        let archive = encoder.finish()?;
+
+        // Act
+        let packages = parse_meta_archive(&archive)?;
+
+        // Assert
+        let names = packages
+            .iter()
+            .map(|pkg| pkg.basic.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["python-foo", "foo-docs", "bar-git"]);
+
+        let foo = packages[0].additional.as_ref().unwrap();
+        assert_eq!(foo.package_base.as_deref(), Some("foo"));
+        assert_eq!(foo.split_packages, vec!["foo-docs"]);
+        assert_eq!(foo.git_clone_url, "https://aur.archlinux.org/foo.git");
+        assert!(packages.iter().all(|pkg| pkg.comments.is_empty()));
+
+        let bar = &packages[2];
+        assert_eq!(bar.basic.maintainer, "");
+        assert_eq!(bar.dependencies.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_plain_json_archive() -> Result<()> {
+        // Act
+        let packages =
+            parse_meta_archive(include_bytes!("../tests/fixtures/packages_meta_ext.json"))?;
+
+        // Assert
+        assert_eq!(packages.len(), 3);
+
+        Ok(())
+    }
+}
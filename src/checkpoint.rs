@@ -0,0 +1,136 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::models::PackageData;
+
+/// Name of the checkpoint file stored next to the BSON files
+const CHECKPOINT_FILE_NAME: &str = "checkpoint.json";
+
+/// Progress of scraping to the file system, saved after every page so the interrupted run can
+/// be resumed.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Numbers of the pages saved to the BSON files
+    pub completed_pages: BTreeSet<u32>,
+    /// Names of the packages saved as partial records, by the number of their page
+    pub failed_packages: BTreeMap<u32, Vec<String>>,
+    /// Number of pages of the packages list read from the scraped pages
+    #[serde(default)]
+    pub pages_count: Option<u32>,
+}
+
+impl Checkpoint {
+    /// Returns the path of the checkpoint file kept in the directory with BSON files.
+    pub fn path(dir: &str) -> PathBuf {
+        Path::new(dir).join(CHECKPOINT_FILE_NAME)
+    }
+
+    /// Reads the checkpoint, an empty one is returned when the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read checkpoint {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Cannot parse checkpoint {}", path.display()))
+    }
+
+    /// Writes the checkpoint to the temporary file first, so the previous checkpoint is kept
+    /// intact if the process is killed while writing.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+
+    /// Marks the page as completed and remembers its packages saved as partial records.
+    pub fn record_page(&mut self, page: u32, packages: &[PackageData]) {
+        let failed = packages
+            .iter()
+            .filter(|pkg| pkg.is_partial())
+            .map(|pkg| pkg.basic.name.clone())
+            .collect::<Vec<_>>();
+
+        self.completed_pages.insert(page);
+        if failed.is_empty() {
+            self.failed_packages.remove(&page);
+        } else {
+            self.failed_packages.insert(page, failed);
+        }
+    }
+
+    pub fn is_completed(&self, page: u32) -> bool {
+        self.completed_pages.contains(&page)
+    }
+
+    /// Returns names of the packages of the page which have to be scraped again.
+    pub fn failed_packages(&self, page: u32) -> &[String] {
+        self.failed_packages
+            .get(&page)
+            .map_or(&[], |names| names.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Checkpoint;
+    use crate::database::shared::create_package_data;
+    use crate::models::PackageData;
+    use anyhow::Result;
+
+    #[test]
+    fn record_pages_and_partial_records() {
+        // Arrange
+        let mut checkpoint = Checkpoint::default();
+        let complete = create_package_data();
+        let partial = PackageData::partial(create_package_data().basic);
+
+        // Act
+        checkpoint.record_page(1, &[create_package_data()]);
+        checkpoint.record_page(2, &[complete, partial]);
+
+        // Assert
+        assert!(checkpoint.is_completed(1));
+        assert!(checkpoint.is_completed(2));
+        assert!(!checkpoint.is_completed(3));
+        assert!(checkpoint.failed_packages(1).is_empty());
+        assert_eq!(checkpoint.failed_packages(2), ["Test"]);
+
+        // Act
+        checkpoint.record_page(2, &[create_package_data()]);
+
+        // Assert
+        assert!(checkpoint.failed_packages(2).is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_and_load_checkpoint() -> Result<()> {
+        // Arrange
+        let dir =
+            std::env::temp_dir().join(format!("aur-raider-checkpoint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = Checkpoint::path(dir.to_str().unwrap());
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.record_page(4, &[PackageData::partial(create_package_data().basic)]);
+
+        // Act
+        let missing = Checkpoint::load(&path)?;
+        checkpoint.save(&path).await?;
+        let loaded = Checkpoint::load(&path)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        // Assert
+        assert_eq!(missing, Checkpoint::default());
+        assert_eq!(loaded, checkpoint);
+
+        Ok(())
+    }
+}
@@ -1,7 +1,12 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 
-use crate::models::PackageData;
+use crate::models::{BasicPackageData, PackageData};
+
+mod connection;
+pub use connection::ConnectionString;
 
 mod redis_io;
 pub use redis_io::RedisIO;
@@ -12,11 +17,32 @@ pub use skytable_io::SkytableIO;
 mod surreal_io;
 pub use surreal_io::SurrealIO;
 
-mod shared;
+#[cfg(test)]
+pub mod shared;
+
+/// Data from the packages list of the stored package.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StoredBasicData {
+    pub basic: BasicPackageData,
+    /// Set when the package is stored as a partial record, without its details
+    pub is_partial: bool,
+}
 
 #[async_trait]
-pub trait DatabasePackageIO {
+pub trait DatabasePackageIO: Send + Sync {
+    fn name(&self) -> &'static str;
     async fn health_check(&self) -> Result<()>;
     async fn insert(&self, pkg: &PackageData) -> Result<()>;
+    #[allow(unused)]
     async fn get(&self, name: &str) -> Result<PackageData>;
+    /// Returns the data from the packages list of all stored packages, except the removed ones.
+    async fn get_all_basic_data(&self) -> Result<Vec<StoredBasicData>>;
+    /// Marks the package deleted from the AUR as a tombstone, its data is kept. Inserting the
+    /// package again clears the mark.
+    async fn mark_removed(&self, name: &str, removed_at: DateTime<Utc>) -> Result<()>;
+}
+
+/// Opens the database described by the connection string, see [`ConnectionString`].
+pub async fn connect(cs: &str) -> Result<Box<dyn DatabasePackageIO>> {
+    cs.parse::<ConnectionString>()?.connect().await
 }
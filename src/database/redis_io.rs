@@ -2,19 +2,25 @@ use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use redis::{self, Client, Commands, Connection};
 
-use crate::models::{Comment, PackageData, PackageDependency};
+use crate::models::{Comment, PackageData, PackageDependency, PackageRelation, Source};
 
-use super::DatabasePackageIO;
+use super::{DatabasePackageIO, StoredBasicData};
 
 pub struct RedisIO {
     client: Client,
 }
 
 impl RedisIO {
+    #[cfg(test)]
     pub fn try_new() -> Result<Self> {
-        let client = redis::Client::open("redis://localhost")?;
+        Self::try_new_with_url("redis://localhost")
+    }
+
+    pub fn try_new_with_url(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url)?;
         Ok(Self { client })
     }
 
@@ -22,15 +28,32 @@ impl RedisIO {
         self.client.get_connection().map_err(|e| anyhow!(e))
     }
 
+    #[cfg(test)]
     fn flushdb(&self) -> Result<()> {
         let mut conn = self.connect()?;
-        redis::cmd("flushdb").query(&mut conn)?;
+        redis::cmd("flushdb").query::<()>(&mut conn)?;
         Ok(())
     }
 }
 
+/// Fields of the package hash renamed since they were written, as pairs of the old and the new
+/// name. Old fields are read when the new ones are missing and removed when details are stored.
+const RENAMED_FIELDS: [(&str, &str); 2] = [("confilcts", "conflicts"), ("license", "licenses")];
+
+fn join_relations(relations: &[PackageRelation]) -> String {
+    relations
+        .iter()
+        .map(|relation| relation.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 #[async_trait]
 impl DatabasePackageIO for RedisIO {
+    fn name(&self) -> &'static str {
+        "Redis"
+    }
+
     async fn health_check(&self) -> Result<()> {
         self.connect()?;
         Ok(())
@@ -39,67 +62,220 @@ impl DatabasePackageIO for RedisIO {
     async fn insert(&self, pkg: &PackageData) -> Result<()> {
         let mut conn = self.connect()?;
 
-        conn.hset_multiple(
+        conn.hset_multiple::<_, _, _, ()>(
             format!("pkgs:{}", pkg.basic.name),
             &[
                 ("popularity", pkg.basic.popularity.to_string().as_str()),
-                ("last_updated", pkg.basic.last_updated.as_str()),
+                (
+                    "last_updated",
+                    pkg.basic.last_updated.timestamp().to_string().as_str(),
+                ),
                 ("description", pkg.basic.description.as_str()),
                 ("maintainer", pkg.basic.maintainer.as_str()),
                 ("version", pkg.basic.version.as_str()),
                 ("votes", pkg.basic.votes.to_string().as_str()),
-                ("path_to_additional_data", pkg.basic.path_to_additional_data.as_str()),
-                ("firstsubmitted", pkg.additional.first_submitted.as_str()),
-                ("gitcloneurl", pkg.additional.git_clone_url.as_str()),
-                ("submitter", pkg.additional.submitter.as_str()),
                 (
-                    "confilcts",
-                    pkg.additional.confilcts.as_ref().map(|s| s.as_str()).unwrap_or(""),
-                ),
-                (
-                    "provides",
-                    pkg.additional.provides.as_ref().map(|s| s.as_str()).unwrap_or(""),
-
-                ),
-                (
-                    "keywords",
-                    pkg.additional.keywords.as_ref().map(|s| s.as_str()).unwrap_or(""),
-                ),
-                (
-                    "license",
-                    pkg.additional.license.as_ref().map(|s| s.as_str()).unwrap_or(""),
+                    "path_to_additional_data",
+                    pkg.basic.path_to_additional_data.as_str(),
                 ),
             ],
         )?;
 
-        conn.sadd("pkgs_set", &pkg.basic.name)?;
+        // Partial records keep the previously stored details untouched
+        if let Some(additional) = &pkg.additional {
+            // Package moved to another base leaves the set of its previous base
+            let previous_base: Option<String> =
+                conn.hget(format!("pkgs:{}", pkg.basic.name), "packagebase")?;
+            if let Some(previous_base) = previous_base.filter(|previous| {
+                !previous.is_empty()
+                    && Some(previous.as_str()) != additional.package_base.as_deref()
+            }) {
+                conn.srem::<_, _, ()>(format!("pkgbases:{}", previous_base), &pkg.basic.name)?;
+            }
+
+            conn.hset_multiple::<_, _, _, ()>(
+                format!("pkgs:{}", pkg.basic.name),
+                &[
+                    (
+                        "firstsubmitted",
+                        additional.first_submitted.timestamp().to_string().as_str(),
+                    ),
+                    ("gitcloneurl", additional.git_clone_url.as_str()),
+                    ("submitter", additional.submitter.as_str()),
+                    ("conflicts", &join_relations(&additional.conflicts)),
+                    ("provides", &join_relations(&additional.provides)),
+                    ("keywords", &additional.keywords.join(",")),
+                    ("licenses", &additional.licenses.join(",")),
+                    (
+                        "packagebase",
+                        additional.package_base.as_deref().unwrap_or(""),
+                    ),
+                    (
+                        "upstreamurl",
+                        additional.upstream_url.as_deref().unwrap_or(""),
+                    ),
+                    ("groups", &additional.groups.join(",")),
+                    ("replaces", &additional.replaces.join(",")),
+                    ("comaintainers", &additional.co_maintainers.join(",")),
+                    (
+                        "lastpackager",
+                        additional.last_packager.as_deref().unwrap_or(""),
+                    ),
+                    (
+                        "outofdate",
+                        additional
+                            .out_of_date
+                            .map(|date| date.timestamp().to_string())
+                            .unwrap_or_default()
+                            .as_str(),
+                    ),
+                ],
+            )?;
+            conn.hdel::<_, _, ()>(
+                format!("pkgs:{}", pkg.basic.name),
+                &RENAMED_FIELDS.map(|(old, _)| old),
+            )?;
+
+            // Sources are kept in a list, so they are read back in the PKGBUILD order
+            let srcs_key = format!("pkgs:{}:srcs", pkg.basic.name);
+            let previous_srcs: Vec<String> = conn.lrange(&srcs_key, 0, -1)?;
+            if !previous_srcs.is_empty() {
+                conn.del::<_, ()>(&previous_srcs)?;
+            }
+            conn.del::<_, ()>(&srcs_key)?;
+
+            for (idx, source) in additional.sources.iter().enumerate() {
+                let src_key = format!("{}:{}", srcs_key, idx + 1);
+                conn.hset_multiple::<_, _, _, ()>(
+                    &src_key,
+                    &[
+                        ("filename", source.filename.as_str()),
+                        ("url", source.url.as_str()),
+                    ],
+                )?;
+                conn.rpush::<_, _, ()>(&srcs_key, &src_key)?;
+            }
+
+            // Split packages are linked through the set of packages built from their base
+            if let Some(base) = &additional.package_base {
+                let base_key = format!("pkgbases:{}", base);
+
+                // Members scraped from the page of the base replace the stored ones
+                if !additional.split_packages.is_empty() {
+                    conn.del::<_, ()>(&base_key)?;
+                    conn.sadd::<_, _, ()>(&base_key, &additional.split_packages)?;
+                }
+                conn.sadd::<_, _, ()>(&base_key, &pkg.basic.name)?;
+            }
+        }
+
+        // Package listed again is no longer a tombstone
+        conn.hdel::<_, _, ()>(format!("pkgs:{}", pkg.basic.name), "removed_at")?;
+        conn.zrem::<_, _, ()>("pkgs_removed", &pkg.basic.name)?;
+
+        conn.sadd::<_, _, ()>("pkgs_set", &pkg.basic.name)?;
+        // Timestamps are stored as Unix seconds, so packages can be range queried by their score
+        conn.zadd::<_, _, _, ()>(
+            "pkgs_by_last_updated",
+            &pkg.basic.name,
+            pkg.basic.last_updated.timestamp(),
+        )?;
+
+        // Re-scraped packages replace the stored comments and dependencies, so the ones removed
+        // from the AUR do not linger
+        if !pkg.is_partial() {
+            for set_key in [
+                format!("pkgs:{}:cmnts", pkg.basic.name),
+                format!("pkgs:{}:deps", pkg.basic.name),
+            ] {
+                let members: Vec<String> = conn.smembers(&set_key)?;
+                if !members.is_empty() {
+                    conn.del::<_, ()>(&members)?;
+                }
+                conn.del::<_, ()>(&set_key)?;
+            }
+        }
 
         for (idx, comment) in pkg.comments.iter().enumerate() {
-            conn.hset_multiple(
+            let mut fields = vec![
+                ("header", comment.header.clone()),
+                ("content", comment.content.clone()),
+                ("is_pinned", comment.is_pinned.to_string()),
+            ];
+            let optional_fields = [
+                ("id", comment.id.map(|id| id.to_string())),
+                ("author", comment.author.clone()),
+                (
+                    "posted_at",
+                    comment.posted_at.map(|t| t.timestamp().to_string()),
+                ),
+                (
+                    "edited_at",
+                    comment.edited_at.map(|t| t.timestamp().to_string()),
+                ),
+                ("edited_by", comment.edited_by.clone()),
+            ];
+            fields.extend(
+                optional_fields
+                    .into_iter()
+                    .filter_map(|(k, v)| v.map(|v| (k, v))),
+            );
+
+            conn.hset_multiple::<_, _, _, ()>(
                 format!("pkgs:{}:cmnts:{}", pkg.basic.name, idx + 1),
-                &[("header", &comment.header), ("content", &comment.content)],
+                &fields,
             )?;
 
-            conn.sadd(
+            conn.sadd::<_, _, ()>(
                 format!("pkgs:{}:cmnts", pkg.basic.name),
                 format!("pkgs:{}:cmnts:{}", pkg.basic.name, idx + 1),
             )?;
         }
 
-        for dependency in &pkg.dependencies {
-            for dep in &dependency.packages {
-                conn.rpush(
-                    format!("pkgs:{}:deps:{}", pkg.basic.name, dependency.group),
-                    dep,
-                )?;
-            }
+        for (idx, dependency) in pkg.dependencies.iter().enumerate() {
+            let mut fields = vec![
+                ("name", dependency.name.clone()),
+                ("kind", dependency.kind.as_str().to_string()),
+            ];
+            let optional_fields = [
+                (
+                    "constraint",
+                    dependency.constraint.as_ref().map(|c| c.to_string()),
+                ),
+                ("description", dependency.description.clone()),
+                ("is_aur", dependency.is_aur.map(|is_aur| is_aur.to_string())),
+                (
+                    "providers",
+                    Some(dependency.providers.join(",")).filter(|p| !p.is_empty()),
+                ),
+            ];
+            fields.extend(
+                optional_fields
+                    .into_iter()
+                    .filter_map(|(k, v)| v.map(|v| (k, v))),
+            );
+
+            conn.hset_multiple::<_, _, _, ()>(
+                format!("pkgs:{}:deps:{}", pkg.basic.name, idx + 1),
+                &fields,
+            )?;
 
-            conn.sadd(
+            conn.sadd::<_, _, ()>(
                 format!("pkgs:{}:deps", pkg.basic.name),
-                format!("pkgs:{}:deps:{}", pkg.basic.name, dependency.group),
+                format!("pkgs:{}:deps:{}", pkg.basic.name, idx + 1),
             )?;
         }
 
+        // Partial records keep the previously stored dependants untouched
+        if !pkg.is_partial() {
+            let key = format!("pkgs:{}:reqs", pkg.basic.name);
+            conn.del::<_, ()>(&key)?;
+
+            if !pkg.required_by.is_empty() {
+                conn.sadd::<_, _, ()>(&key, &pkg.required_by)?;
+            }
+        }
+
         Ok(())
     }
 
@@ -108,6 +284,11 @@ impl DatabasePackageIO for RedisIO {
 
         let mut pkg_dict: HashMap<String, String> = conn.hgetall(format!("pkgs:{}", name))?;
         pkg_dict.insert("name".into(), name.into());
+        for (old, new) in RENAMED_FIELDS {
+            if let Some(value) = pkg_dict.remove(old) {
+                pkg_dict.entry(new.into()).or_insert(value);
+            }
+        }
 
         let mut pkg = PackageData::try_from(pkg_dict).map_err(|e| anyhow!(e))?;
 
@@ -122,20 +303,74 @@ impl DatabasePackageIO for RedisIO {
 
         pkg.comments = comments;
 
-        let group_list: Vec<String> = conn.smembers(format!("pkgs:{}:deps", pkg.basic.name))?;
+        let deps_list: Vec<String> = conn.smembers(format!("pkgs:{}:deps", pkg.basic.name))?;
 
         let mut dependencies = vec![];
 
-        for group in group_list {
-            let packages: Vec<String> = conn.lrange(&group, 0, -1)?;
-
-            dependencies.push(PackageDependency { group, packages });
+        for dep in deps_list {
+            let dep_dict: HashMap<String, String> = conn.hgetall(dep)?;
+            dependencies.push(PackageDependency::try_from(dep_dict)?);
         }
 
         pkg.dependencies = dependencies;
+        pkg.required_by = conn.smembers(format!("pkgs:{}:reqs", pkg.basic.name))?;
+
+        if let Some(additional) = &mut pkg.additional {
+            let srcs_list: Vec<String> =
+                conn.lrange(format!("pkgs:{}:srcs", pkg.basic.name), 0, -1)?;
+
+            for src in srcs_list {
+                let src_dict: HashMap<String, String> = conn.hgetall(src)?;
+                additional.sources.push(Source::try_from(src_dict)?);
+            }
+
+            if let Some(base) = &additional.package_base {
+                let members: Vec<String> = conn.smembers(format!("pkgbases:{}", base))?;
+                additional.split_packages = members
+                    .into_iter()
+                    .filter(|member| member != &pkg.basic.name)
+                    .collect();
+            }
+        }
 
         Ok(pkg)
     }
+
+    async fn get_all_basic_data(&self) -> Result<Vec<StoredBasicData>> {
+        let mut conn = self.connect()?;
+        let names: Vec<String> = conn.smembers("pkgs_set")?;
+        let mut packages = vec![];
+
+        for name in names {
+            let mut pkg_dict: HashMap<String, String> = conn.hgetall(format!("pkgs:{}", name))?;
+            pkg_dict.insert("name".into(), name);
+
+            let pkg = PackageData::try_from(pkg_dict).map_err(|e| anyhow!(e))?;
+            packages.push(StoredBasicData {
+                is_partial: pkg.is_partial(),
+                basic: pkg.basic,
+            });
+        }
+
+        Ok(packages)
+    }
+
+    async fn mark_removed(&self, name: &str, removed_at: DateTime<Utc>) -> Result<()> {
+        let mut conn = self.connect()?;
+
+        conn.hset::<_, _, _, ()>(
+            format!("pkgs:{}", name),
+            "removed_at",
+            removed_at.timestamp(),
+        )?;
+        // Tombstones are moved out of the sets of the listed packages to their own sorted set,
+        // scored by the removal time
+        conn.srem::<_, _, ()>("pkgs_set", name)?;
+        conn.zrem::<_, _, ()>("pkgs_by_last_updated", name)?;
+        conn.zadd::<_, _, _, ()>("pkgs_removed", name, removed_at.timestamp())?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -145,7 +380,10 @@ mod test {
         shared::{assert_pkg, create_package_data},
         DatabasePackageIO,
     };
+    use crate::models::Source;
     use anyhow::Result;
+    use chrono::{TimeZone, Utc};
+    use redis::Commands;
 
     #[test]
     fn success_init_when_database_is_up() {
@@ -153,7 +391,7 @@ mod test {
         let redis = RedisIO::try_new();
 
         // Assert
-        assert_eq!(redis.is_ok(), true);
+        assert!(redis.is_ok());
     }
 
     #[test]
@@ -165,7 +403,7 @@ mod test {
         let con = redis.connect();
 
         // Assert
-        assert_eq!(con.is_ok(), true);
+        assert!(con.is_ok());
     }
 
     #[tokio::test]
@@ -184,4 +422,113 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn replace_comments_of_reinserted_package() -> Result<()> {
+        // Arrange
+        let redis = RedisIO::try_new()?;
+        let mut generated_pkg = create_package_data();
+
+        // Act
+        redis.flushdb()?;
+        redis.insert(&generated_pkg).await?;
+        generated_pkg.comments.pop();
+        redis.insert(&generated_pkg).await?;
+        let retreived_pkg = redis.get("Test").await?;
+        let basic_data = redis.get_all_basic_data().await?;
+
+        // Assert
+        assert_pkg(&retreived_pkg, &generated_pkg);
+        assert_eq!(basic_data.len(), 1);
+        assert_eq!(basic_data[0].basic.version, generated_pkg.basic.version);
+        assert!(!basic_data[0].is_partial);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replace_sources_and_split_packages_of_reinserted_package() -> Result<()> {
+        // Arrange
+        let redis = RedisIO::try_new()?;
+        let mut generated_pkg = create_package_data();
+        let additional = generated_pkg.additional.as_mut().unwrap();
+        additional.sources.push(Source {
+            filename: "test.patch".into(),
+            url: "test.patch".into(),
+        });
+        additional.split_packages = vec!["Test-docs".into(), "Test-man".into()];
+
+        // Act
+        redis.flushdb()?;
+        redis.insert(&generated_pkg).await?;
+        let additional = generated_pkg.additional.as_mut().unwrap();
+        additional.sources.pop();
+        additional.split_packages = vec!["Test-man".into()];
+        redis.insert(&generated_pkg).await?;
+        let retreived_pkg = redis.get("Test").await?;
+        let stale_source: bool = redis.connect()?.exists("pkgs:Test:srcs:2")?;
+
+        // Assert
+        let additional = retreived_pkg.additional.unwrap();
+        assert_eq!(additional.sources.len(), 1);
+        assert_eq!(additional.split_packages, vec!["Test-man"]);
+        assert!(!stale_source);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mark_removed_package_as_tombstone() -> Result<()> {
+        // Arrange
+        let redis = RedisIO::try_new()?;
+        let generated_pkg = create_package_data();
+        let removed_at = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+        // Act
+        redis.flushdb()?;
+        redis.insert(&generated_pkg).await?;
+        redis.mark_removed("Test", removed_at).await?;
+        let tombstone = redis.get("Test").await?;
+        let listed = redis.get_all_basic_data().await?;
+        redis.insert(&generated_pkg).await?;
+        let restored = redis.get("Test").await?;
+
+        // Assert
+        assert_eq!(tombstone.removed_at, Some(removed_at));
+        assert!(tombstone.additional.is_some());
+        assert!(listed.is_empty());
+        assert_eq!(restored.removed_at, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_fields_stored_under_old_names() -> Result<()> {
+        // Arrange
+        let redis = RedisIO::try_new()?;
+        let generated_pkg = create_package_data();
+
+        // Act
+        redis.flushdb()?;
+        redis.insert(&generated_pkg).await?;
+        let mut conn = redis.connect()?;
+        conn.hdel::<_, _, ()>("pkgs:Test", &["conflicts", "licenses"])?;
+        conn.hset_multiple::<_, _, _, ()>(
+            "pkgs:Test",
+            &[("confilcts", "foo,bar>=2.0"), ("license", "GPL3")],
+        )?;
+        let legacy_pkg = redis.get("Test").await?;
+        redis.insert(&generated_pkg).await?;
+        let migrated_fields: Vec<String> = conn.hkeys("pkgs:Test")?;
+
+        // Assert
+        let additional = legacy_pkg.additional.unwrap();
+        assert_eq!(additional.conflicts.len(), 2);
+        assert_eq!(additional.conflicts[1].to_string(), "bar>=2.0");
+        assert_eq!(additional.licenses, vec!["GPL3"]);
+        assert!(!migrated_fields.contains(&"confilcts".to_string()));
+        assert!(!migrated_fields.contains(&"license".to_string()));
+
+        Ok(())
+    }
 }
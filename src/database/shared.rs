@@ -1,5 +1,8 @@
+use chrono::{TimeZone, Utc};
+
 use crate::models::{
-    AdditionalPackageData, BasicPackageData, Comment, PackageData, PackageDependency,
+    AdditionalPackageData, BasicPackageData, Comment, ConstraintOperator, DependencyKind,
+    PackageData, PackageDependency, PackageRelation, Source, VersionConstraint,
 };
 
 pub fn create_package_data() -> PackageData {
@@ -11,41 +14,96 @@ pub fn create_package_data() -> PackageData {
             popularity: 6.2,
             maintainer: "Tester".into(),
             description: "Sample description".into(),
-            last_updated: "2012".into(),
+            last_updated: Utc.with_ymd_and_hms(2012, 1, 1, 10, 0, 0).unwrap(),
             path_to_additional_data: "/test".into(),
         },
-        additional: AdditionalPackageData {
-            license: None,
-            keywords: None,
-            provides: None,
-            confilcts: None,
+        additional: Some(AdditionalPackageData {
+            licenses: vec!["MIT".into()],
+            keywords: vec!["test".into(), "sample".into()],
+            provides: vec![PackageRelation {
+                name: "test-runtime".into(),
+                constraint: Some(VersionConstraint {
+                    operator: ConstraintOperator::Equal,
+                    version: "1.2".into(),
+                }),
+            }],
+            conflicts: vec![],
             submitter: "Tester".into(),
             git_clone_url: "some git url".into(),
-            first_submitted: "2011".into(),
-        },
+            first_submitted: Utc.with_ymd_and_hms(2011, 1, 1, 10, 0, 0).unwrap(),
+            package_base: Some("test-base".into()),
+            split_packages: vec!["Test-docs".into()],
+            sources: vec![Source {
+                filename: "test.tar.gz".into(),
+                url: "https://example.com/test.tar.gz".into(),
+            }],
+            upstream_url: Some("https://example.com".into()),
+            groups: vec!["tests".into()],
+            replaces: vec![],
+            co_maintainers: vec!["Helper".into()],
+            last_packager: Some("Tester".into()),
+            out_of_date: Some(Utc.with_ymd_and_hms(2012, 2, 1, 0, 0, 0).unwrap()),
+            votes: Some(100),
+            popularity: Some(0.5),
+        }),
         comments: vec![
             Comment {
                 header: "Someone wrote at 14:15".into(),
                 content: "Cool package".into(),
+                is_pinned: true,
+                id: Some(1),
+                author: Some("Someone".into()),
+                posted_at: Some(Utc.with_ymd_and_hms(2012, 2, 1, 14, 15, 0).unwrap()),
+                edited_at: None,
+                edited_by: None,
             },
             Comment {
                 header: "Foo wrote at 20:30".into(),
                 content: "Not bad".into(),
+                is_pinned: false,
+                id: Some(2),
+                author: Some("Foo".into()),
+                posted_at: Some(Utc.with_ymd_and_hms(2012, 2, 2, 20, 30, 0).unwrap()),
+                edited_at: Some(Utc.with_ymd_and_hms(2012, 2, 2, 20, 45, 0).unwrap()),
+                edited_by: Some("Foo".into()),
             },
         ],
 
-        dependencies: vec![PackageDependency {
-            group: "abc".into(),
-            packages: vec!["aaa".into(), "bbb".into(), "ccc".into()],
-        }],
+        dependencies: vec![
+            PackageDependency {
+                name: "abc".into(),
+                kind: DependencyKind::Depends,
+                constraint: Some(VersionConstraint {
+                    operator: ConstraintOperator::GreaterOrEqual,
+                    version: "1.0".into(),
+                }),
+                description: None,
+                is_aur: Some(false),
+                providers: vec!["aaa".into(), "bbb".into(), "ccc".into()],
+            },
+            PackageDependency {
+                name: "def".into(),
+                kind: DependencyKind::OptDepends,
+                constraint: None,
+                description: Some("for tests".into()),
+                is_aur: None,
+                providers: vec![],
+            },
+        ],
+        required_by: vec!["ghi".into(), "jkl".into()],
+        removed_at: None,
     }
 }
 
 pub fn assert_pkg(retreived_pkg: &PackageData, generated_pkg: &PackageData) {
-        assert_eq!(retreived_pkg.basic.name, generated_pkg.basic.name);
-        assert_eq!(retreived_pkg.comments.len(), generated_pkg.comments.len());
-        assert_eq!(
-            retreived_pkg.dependencies.len(),
-            generated_pkg.dependencies.len()
-        );
+    assert_eq!(retreived_pkg.basic.name, generated_pkg.basic.name);
+    assert_eq!(retreived_pkg.comments.len(), generated_pkg.comments.len());
+    assert_eq!(
+        retreived_pkg.dependencies.len(),
+        generated_pkg.dependencies.len()
+    );
+    assert_eq!(
+        retreived_pkg.required_by.len(),
+        generated_pkg.required_by.len()
+    );
 }
@@ -1,5 +1,8 @@
+use std::collections::HashSet;
+
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 use skytable::{
     actions::Actions,
@@ -14,12 +17,14 @@ const BASIC_PKGS_TABLE: &str = "pkgs:basic";
 const ADDITIONAL_PKGS_TABLE: &str = "pkgs:additional";
 const COMMENTS_TABLE: &str = "pkgs:comments";
 const DEPENDENCIES_TABLE: &str = "pkgs:dependencies";
+const REQUIRED_BY_TABLE: &str = "pkgs:required_by";
+const REMOVED_TABLE: &str = "pkgs:removed";
 
 use crate::models::{
-    AdditionalPackageData, BasicPackageData, Comment, PackageData, PackageDependency,
+    parse_timestamp, AdditionalPackageData, BasicPackageData, Comment, PackageData, PackageDependency,
 };
 
-use super::DatabasePackageIO;
+use super::{DatabasePackageIO, StoredBasicData};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Comments {
@@ -36,8 +41,13 @@ pub struct SkytableIO {
 }
 
 impl SkytableIO {
+    #[cfg(test)]
     pub fn try_new() -> Result<Self> {
-        let pool = pool::get("127.0.0.1", 2003, 16)?;
+        Self::try_new_with_address("127.0.0.1", 2003)
+    }
+
+    pub fn try_new_with_address(host: &str, port: u16) -> Result<Self> {
+        let pool = pool::get(host, port, 16)?;
         Ok(Self { pool })
     }
 
@@ -67,10 +77,23 @@ impl SkytableIO {
         .set_ktype(KeymapType::Str)
         .set_vtype(KeymapType::Other("list<binstr>".to_owned()));
 
+        check_err(conn.create_table(pkgs_table))?;
+
+        let pkgs_table = Keymap::new(REQUIRED_BY_TABLE)
+            .set_ktype(KeymapType::Str)
+            .set_vtype(KeymapType::Other("list<str>".to_owned()));
+
+        check_err(conn.create_table(pkgs_table))?;
+
+        let pkgs_table = Keymap::new(REMOVED_TABLE)
+            .set_ktype(KeymapType::Str)
+            .set_vtype(KeymapType::Str);
+
         check_err(conn.create_table(pkgs_table))?;
         Ok(())
     }
 
+    #[cfg(test)]
     fn flushdb(&self) -> Result<()> {
         let mut conn = self.pool.get()?;
         conn.flushdb()?;
@@ -91,6 +114,10 @@ fn check_err<T>(res: SkyResult<T>) -> Result<()> {
 
 #[async_trait]
 impl DatabasePackageIO for SkytableIO {
+    fn name(&self) -> &'static str {
+        "Skytable"
+    }
+
     async fn health_check(&self) -> Result<()> {
         self.pool.get()?;
         Ok(())
@@ -100,28 +127,48 @@ impl DatabasePackageIO for SkytableIO {
         let mut conn = self.pool.get()?;
         let pkg_name = pkg.basic.name.clone();
 
+        // Packages are upserted, so re-scraped packages replace the stored ones
         conn.switch(BASIC_PKGS_TABLE)?;
-        conn.set(&pkg_name, &pkg.basic)?;
-
-        conn.switch(ADDITIONAL_PKGS_TABLE)?;
-        conn.set(&pkg_name, &pkg.additional)?;
+        conn.run_query_raw(Query::new().arg("USET").arg(&pkg_name).arg(&pkg.basic))?;
 
-        conn.switch(COMMENTS_TABLE)?;
-        conn.run_query_raw(Query::new().arg("LSET").arg(&pkg.basic.name))?;
-        conn.run_query_raw(Query::new().arg("LMOD").arg(&pkg.basic.name).arg("CLEAR"))?;
+        // Package listed again is no longer a tombstone
+        conn.switch(REMOVED_TABLE)?;
+        conn.del(&pkg_name)?;
 
-        for comment in &pkg.comments {
-            let query = Query::new().arg("LMOD").arg(&pkg.basic.name).arg("PUSH").arg(comment);
-            conn.run_query_raw(query)?;
+        // Lists are created for new packages, existing lists are left as they are
+        for table in [COMMENTS_TABLE, DEPENDENCIES_TABLE, REQUIRED_BY_TABLE] {
+            conn.switch(table)?;
+            conn.run_query_raw(Query::new().arg("LSET").arg(&pkg.basic.name))?;
         }
 
-        conn.switch(DEPENDENCIES_TABLE)?;
-        conn.run_query_raw(Query::new().arg("LSET").arg(&pkg.basic.name))?;
-        conn.run_query_raw(Query::new().arg("LMOD").arg(&pkg.basic.name).arg("CLEAR"))?;
+        // Partial records keep the previously stored details, comments and dependencies untouched
+        if let Some(additional) = &pkg.additional {
+            conn.switch(ADDITIONAL_PKGS_TABLE)?;
+            conn.run_query_raw(Query::new().arg("USET").arg(&pkg_name).arg(additional))?;
+
+            conn.switch(COMMENTS_TABLE)?;
+            conn.run_query_raw(Query::new().arg("LMOD").arg(&pkg.basic.name).arg("CLEAR"))?;
+
+            for comment in &pkg.comments {
+                let query = Query::new().arg("LMOD").arg(&pkg.basic.name).arg("PUSH").arg(comment);
+                conn.run_query_raw(query)?;
+            }
+
+            conn.switch(DEPENDENCIES_TABLE)?;
+            conn.run_query_raw(Query::new().arg("LMOD").arg(&pkg.basic.name).arg("CLEAR"))?;
+
+            for dependency in &pkg.dependencies {
+                let query = Query::new().arg("LMOD").arg(&pkg.basic.name).arg("PUSH").arg(dependency);
+                conn.run_query_raw(query)?;
+            }
+
+            conn.switch(REQUIRED_BY_TABLE)?;
+            conn.run_query_raw(Query::new().arg("LMOD").arg(&pkg.basic.name).arg("CLEAR"))?;
 
-        for dependency in &pkg.dependencies {
-            let query = Query::new().arg("LMOD").arg(&pkg.basic.name).arg("PUSH").arg(dependency);
-            conn.run_query_raw(query)?;
+            for dependant in &pkg.required_by {
+                let query = Query::new().arg("LMOD").arg(&pkg.basic.name).arg("PUSH").arg(dependant);
+                conn.run_query_raw(query)?;
+            }
         }
    
         Ok(())
@@ -134,7 +181,10 @@ impl DatabasePackageIO for SkytableIO {
         let basic: BasicPackageData = conn.get(name)?;
 
         conn.switch(ADDITIONAL_PKGS_TABLE)?;
-        let additional: AdditionalPackageData = conn.get(name)?;
+        let additional: Option<AdditionalPackageData> = match conn.exists(name)? {
+            0 => None,
+            _ => Some(conn.get(name)?),
+        };
 
         conn.switch(COMMENTS_TABLE)?;
         let comments: Comments = conn.run_query(Query::new().arg("LGET").arg(name))?;
@@ -142,13 +192,60 @@ impl DatabasePackageIO for SkytableIO {
         conn.switch(DEPENDENCIES_TABLE)?;
         let dependencies: Dependencies = conn.run_query(Query::new().arg("LGET").arg(name))?;
 
+        conn.switch(REQUIRED_BY_TABLE)?;
+        let required_by: Vec<String> = conn.run_query(Query::new().arg("LGET").arg(name))?;
+
+        conn.switch(REMOVED_TABLE)?;
+        let removed_at = match conn.exists(name)? {
+            0 => None,
+            _ => Some(parse_timestamp(&conn.get::<String>(name)?, "removed_at")?),
+        };
+
         Ok(PackageData {
             basic,
             additional,
             comments: comments.data,
             dependencies: dependencies.data,
+            required_by,
+            removed_at,
         })
     }
+
+    async fn get_all_basic_data(&self) -> Result<Vec<StoredBasicData>> {
+        let mut conn = self.pool.get()?;
+
+        conn.switch(REMOVED_TABLE)?;
+        let count = conn.dbsize()?;
+        let removed: HashSet<String> = conn.lskeys::<Vec<String>>(count)?.into_iter().collect();
+
+        // Partial records are stored without details
+        conn.switch(ADDITIONAL_PKGS_TABLE)?;
+        let count = conn.dbsize()?;
+        let detailed: HashSet<String> = conn.lskeys::<Vec<String>>(count)?.into_iter().collect();
+
+        conn.switch(BASIC_PKGS_TABLE)?;
+        let count = conn.dbsize()?;
+        let names: Vec<String> = conn.lskeys(count)?;
+
+        let mut packages = vec![];
+        for name in names.iter().filter(|name| !removed.contains(*name)) {
+            packages.push(StoredBasicData {
+                basic: conn.get(name)?,
+                is_partial: !detailed.contains(name),
+            });
+        }
+
+        Ok(packages)
+    }
+
+    async fn mark_removed(&self, name: &str, removed_at: DateTime<Utc>) -> Result<()> {
+        let mut conn = self.pool.get()?;
+
+        conn.switch(REMOVED_TABLE)?;
+        conn.run_query_raw(Query::new().arg("USET").arg(name).arg(removed_at.timestamp().to_string()))?;
+
+        Ok(())
+    }
 }
 
 impl IntoSkyhashBytes for &BasicPackageData {
@@ -240,7 +337,9 @@ mod test {
         shared::{assert_pkg, create_package_data},
         DatabasePackageIO,
     };
+    use crate::models::PackageData;
     use anyhow::Result;
+    use chrono::{TimeZone, Utc};
 
     #[test]
     fn skytable_success_init_when_database_is_up() -> Result<()> {
@@ -272,4 +371,75 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn keep_details_of_package_reinserted_as_partial_record() -> Result<()> {
+        // Arrange
+        let skytable = SkytableIO::try_new()?;
+        let generated_pkg = create_package_data();
+        let mut partial_pkg = PackageData::partial(create_package_data().basic);
+        partial_pkg.basic.votes = 250;
+
+        // Act
+        skytable.flushdb()?;
+        skytable.create_tables()?;
+        skytable.insert(&generated_pkg).await?;
+        skytable.insert(&partial_pkg).await?;
+        let retreived_pkg = skytable.get("Test").await?;
+
+        // Assert
+        assert_eq!(retreived_pkg.basic.votes, 250);
+        assert!(retreived_pkg.additional.is_some());
+        assert_eq!(retreived_pkg.comments.len(), generated_pkg.comments.len());
+        assert_eq!(retreived_pkg.dependencies.len(), generated_pkg.dependencies.len());
+        assert_eq!(retreived_pkg.required_by, generated_pkg.required_by);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_basic_data_of_all_packages() -> Result<()> {
+        // Arrange
+        let skytable = SkytableIO::try_new()?;
+        let generated_pkg = create_package_data();
+
+        // Act
+        skytable.flushdb()?;
+        skytable.create_tables()?;
+        skytable.insert(&generated_pkg).await?;
+        let basic_data = skytable.get_all_basic_data().await?;
+
+        // Assert
+        assert_eq!(basic_data.len(), 1);
+        assert_eq!(basic_data[0].basic.name, generated_pkg.basic.name);
+        assert!(!basic_data[0].is_partial);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mark_removed_package_as_tombstone() -> Result<()> {
+        // Arrange
+        let skytable = SkytableIO::try_new()?;
+        let generated_pkg = create_package_data();
+        let removed_at = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+        // Act
+        skytable.flushdb()?;
+        skytable.create_tables()?;
+        skytable.insert(&generated_pkg).await?;
+        skytable.mark_removed("Test", removed_at).await?;
+        let tombstone = skytable.get("Test").await?;
+        let listed = skytable.get_all_basic_data().await?;
+        skytable.insert(&generated_pkg).await?;
+        let restored = skytable.get("Test").await?;
+
+        // Assert
+        assert_eq!(tombstone.removed_at, Some(removed_at));
+        assert!(tombstone.additional.is_some());
+        assert!(listed.is_empty());
+        assert_eq!(restored.removed_at, None);
+
+        Ok(())
+    }
 }
@@ -1,14 +1,18 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use surrealdb::engine::remote::ws::{Client, Ws};
 use surrealdb::opt::auth::Root;
-use surrealdb::sql::Thing;
+use surrealdb::sql::{self, Thing, Value};
 use surrealdb::Surreal;
 
-use crate::models::PackageData;
+use crate::{
+    models::{parse_timestamp, timestamp, BasicPackageData, PackageData},
+    serialization::TIMESTAMP_FIELDS,
+};
 
-use super::DatabasePackageIO;
+use super::{DatabasePackageIO, StoredBasicData};
 
 #[derive(Debug, Deserialize)]
 struct Record {
@@ -16,57 +20,114 @@ struct Record {
     id: Thing,
 }
 
-type SurResult<T> = Result<T, surrealdb::Error>;
+/// Record holding only the data from the packages list, used to update it alone. Updated
+/// package is no longer a tombstone.
+#[derive(Debug, Serialize)]
+struct BasicRecord {
+    basic: BasicPackageData,
+    #[serde(with = "timestamp::option")]
+    removed_at: Option<DateTime<Utc>>,
+}
+
+/// Tombstone mark merged into the record of the removed package.
+#[derive(Debug, Serialize)]
+struct RemovedRecord {
+    #[serde(with = "timestamp")]
+    removed_at: DateTime<Utc>,
+}
 
 pub struct SurrealIO {
     db: Surreal<Client>,
 }
 
 impl SurrealIO {
+    #[cfg(test)]
     pub async fn try_new() -> Result<Self> {
-        let db = Surreal::new::<Ws>("127.0.0.1:8000").await?;
+        Self::try_new_with_options("127.0.0.1:8000", Some(("root", "root")), "aur", "packages")
+            .await
+    }
 
-        db.signin(Root {
-            username: "root",
-            password: "root",
-        })
-        .await?;
+    pub async fn try_new_with_options(
+        address: &str,
+        credentials: Option<(&str, &str)>,
+        namespace: &str,
+        database: &str,
+    ) -> Result<Self> {
+        let db = Surreal::new::<Ws>(address).await?;
 
-        db.use_ns("aur").use_db("packages").await?;
+        if let Some((username, password)) = credentials {
+            db.signin(Root { username, password }).await?;
+        }
+
+        db.use_ns(namespace).use_db(database).await?;
 
         Ok(Self { db })
     }
 
+    #[cfg(test)]
     async fn delete(&self, name: &str) -> Result<()> {
         let _: Option<PackageData> = self.db.delete(("pkgs", name)).await?;
         Ok(())
     }
 }
 
-fn skip_allready_exist_error<T>(res: SurResult<T>) -> Result<()> {
-    if let Err(e) = res {
-        if !e.to_string().contains("already exists") {
-            return Err(e.into());
+/// Converts the package to the Surreal value with timestamps stored as datetimes, so they can be
+/// compared and range filtered in queries.
+fn to_surreal_value<T: Serialize>(pkg: &T) -> Result<Value> {
+    let mut value = sql::json(&serde_json::to_string(pkg)?)?;
+    timestamps_to_datetimes(&mut value);
+    Ok(value)
+}
+
+fn timestamps_to_datetimes(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    Value::Strand(timestamp) if TIMESTAMP_FIELDS.contains(&key.as_str()) => {
+                        if let Ok(timestamp) = parse_timestamp(timestamp, "timestamp") {
+                            *value = Value::Datetime(timestamp.into());
+                        }
+                    }
+                    _ => timestamps_to_datetimes(value),
+                }
+            }
         }
+        Value::Array(array) => array.iter_mut().for_each(timestamps_to_datetimes),
+        _ => {}
     }
-
-    Ok(())
 }
 
 #[async_trait]
 impl DatabasePackageIO for SurrealIO {
+    fn name(&self) -> &'static str {
+        "Surreal"
+    }
+
     async fn health_check(&self) -> Result<()> {
         self.db.health().await?;
         Ok(())
     }
 
     async fn insert(&self, pkg: &PackageData) -> Result<()> {
-        let res: SurResult<Record> = self
-            .db
-            .create(("pkgs", &pkg.basic.name))
-            .content(&pkg)
-            .await;
-        skip_allready_exist_error(res)?;
+        // Partial records keep the previously stored details untouched
+        if pkg.is_partial() {
+            let basic = BasicRecord {
+                basic: pkg.basic.clone(),
+                removed_at: None,
+            };
+            let _: Option<Record> = self
+                .db
+                .update(("pkgs", &pkg.basic.name))
+                .merge(to_surreal_value(&basic)?)
+                .await?;
+        } else {
+            let _: Option<Record> = self
+                .db
+                .update(("pkgs", &pkg.basic.name))
+                .content(to_surreal_value(pkg)?)
+                .await?;
+        }
 
         Ok(())
     }
@@ -75,6 +136,27 @@ impl DatabasePackageIO for SurrealIO {
         let pkg: PackageData = self.db.select(("pkgs", name)).await?;
         Ok(pkg)
     }
+
+    async fn get_all_basic_data(&self) -> Result<Vec<StoredBasicData>> {
+        // Partial records are stored without details
+        let records: Vec<StoredBasicData> = self
+            .db
+            .query("SELECT basic, !additional AS is_partial FROM pkgs WHERE !removed_at")
+            .await?
+            .take(0)?;
+        Ok(records)
+    }
+
+    async fn mark_removed(&self, name: &str, removed_at: DateTime<Utc>) -> Result<()> {
+        let removed = RemovedRecord { removed_at };
+        let _: Option<Record> = self
+            .db
+            .update(("pkgs", name))
+            .merge(to_surreal_value(&removed)?)
+            .await?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -82,6 +164,7 @@ mod test {
     use super::SurrealIO;
     use crate::database::{shared::create_package_data, DatabasePackageIO};
     use anyhow::Result;
+    use chrono::{TimeZone, Utc};
 
     #[tokio::test]
     async fn success_surrealio_init_when_database_is_up() {
@@ -109,4 +192,29 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn mark_removed_package_as_tombstone() -> Result<()> {
+        // Arrange
+        let db = SurrealIO::try_new().await?;
+        let generated_pkg = create_package_data();
+        let removed_at = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+
+        // Act
+        db.insert(&generated_pkg).await?;
+        db.mark_removed("Test", removed_at).await?;
+        let tombstone = db.get("Test").await?;
+        let listed = db.get_all_basic_data().await?;
+        db.insert(&generated_pkg).await?;
+        let restored = db.get("Test").await?;
+        db.delete("Test").await?;
+
+        // Assert
+        assert_eq!(tombstone.removed_at, Some(removed_at));
+        assert!(tombstone.additional.is_some());
+        assert!(listed.iter().all(|stored| stored.basic.name != "Test"));
+        assert_eq!(restored.removed_at, None);
+
+        Ok(())
+    }
 }
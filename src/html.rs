@@ -1,23 +1,293 @@
-use scraper::ElementRef;
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Node};
 
-pub fn delete_tags(mut html: String) -> String {
-    html = html.lines().map(|l| l.trim()).collect();
+pub fn extract_attribute_value(el: ElementRef, attr_name: &str) -> String {
+    el.value()
+        .attr(attr_name)
+        .map_or("".to_string(), |s| s.to_string())
+}
+
+/// Returns the text of the element with whitespace sequences collapsed into single spaces.
+pub fn element_text(el: ElementRef) -> String {
+    el.text()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Converts the HTML rendered by the AUR from comment Markdown back to the Markdown. Paragraphs,
+/// code blocks, links, lists, quotes and emphasis are preserved.
+pub fn to_markdown(el: ElementRef) -> String {
+    let mut writer = MarkdownWriter::default();
+    writer.render_children(*el);
+    writer.out.trim_end().to_string()
+}
+
+#[derive(Default)]
+struct MarkdownWriter {
+    out: String,
+    /// Number of line breaks which will be written before the next text
+    pending_breaks: usize,
+    /// Prefixes of the lines nested in quotes and list items
+    prefixes: Vec<String>,
+    /// Set after the list item marker, so the first block of the item stays on its line
+    after_marker: bool,
+}
+
+impl MarkdownWriter {
+    fn render_children(&mut self, node: NodeRef<Node>) {
+        for child in node.children() {
+            self.render(child);
+        }
+    }
+
+    fn render(&mut self, node: NodeRef<Node>) {
+        let el = match node.value() {
+            Node::Text(text) => return self.write_inline(text),
+            Node::Element(el) => el,
+            _ => return,
+        };
+
+        match el.name() {
+            "p" | "div" => {
+                self.block_break(2);
+                self.render_children(node);
+                self.block_break(2);
+            }
+            "br" => self.block_break(1),
+            "hr" => {
+                self.block_break(2);
+                self.write("---");
+                self.block_break(2);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = el.name()[1..].parse().unwrap_or(1);
+                self.block_break(2);
+                self.write(&format!("{} ", "#".repeat(level)));
+                self.render_children(node);
+                self.block_break(2);
+            }
+            "pre" => self.write_code_block(node),
+            "code" => {
+                let code = text_of(node);
+                if code.contains('`') {
+                    self.write(&format!("`` {} ``", code));
+                } else {
+                    self.write(&format!("`{}`", code));
+                }
+            }
+            "a" => {
+                let href = el.attr("href").unwrap_or_default();
+                let text = ElementRef::wrap(node).map(element_text).unwrap_or_default();
+
+                if href.is_empty() {
+                    self.write_inline(&text);
+                } else if text.is_empty() || text == href {
+                    self.write(&format!("<{}>", href));
+                } else {
+                    self.write(&format!("[{}]({})", text, href));
+                }
+            }
+            "img" => {
+                let alt = el.attr("alt").unwrap_or_default();
+                let src = el.attr("src").unwrap_or_default();
+                self.write(&format!("![{}]({})", alt, src));
+            }
+            "strong" | "b" => self.write_wrapped(node, "**"),
+            "em" | "i" => self.write_wrapped(node, "*"),
+            "del" | "s" | "strike" => self.write_wrapped(node, "~~"),
+            "ul" | "ol" => self.write_list(node, el.name() == "ol"),
+            "blockquote" => {
+                self.block_break(2);
+                self.prefixes.push("> ".to_string());
+                self.render_children(node);
+                self.prefixes.pop();
+                self.block_break(2);
+            }
+            "script" | "style" | "form" => {}
+            _ => self.render_children(node),
+        }
+    }
+
+    fn write_wrapped(&mut self, node: NodeRef<Node>, marker: &str) {
+        self.write(marker);
+        self.render_children(node);
+        while self.out.ends_with(' ') {
+            self.out.pop();
+        }
+        self.write(marker);
+    }
+
+    fn write_list(&mut self, node: NodeRef<Node>, ordered: bool) {
+        self.block_break(if self.prefixes.is_empty() { 2 } else { 1 });
+
+        let items = node
+            .children()
+            .filter(|child| matches!(child.value(), Node::Element(el) if el.name() == "li"));
+
+        for (idx, item) in items.enumerate() {
+            let marker = if ordered {
+                format!("{}. ", idx + 1)
+            } else {
+                "- ".to_string()
+            };
+
+            self.block_break(1);
+            self.write(&marker);
+            self.after_marker = true;
+            self.prefixes.push(" ".repeat(marker.len()));
+            self.render_children(item);
+            self.prefixes.pop();
+            self.after_marker = false;
+        }
+
+        self.block_break(2);
+    }
+
+    fn write_code_block(&mut self, node: NodeRef<Node>) {
+        let language = node
+            .children()
+            .filter_map(|child| match child.value() {
+                Node::Element(el) if el.name() == "code" => el.attr("class"),
+                _ => None,
+            })
+            .flat_map(|class| class.split_whitespace())
+            .find_map(|class| class.strip_prefix("language-"))
+            .unwrap_or_default();
+        let code = text_of(node);
+
+        self.block_break(2);
+        self.write(&format!("```{}", language));
+        for line in code.trim_end_matches('\n').split('\n') {
+            self.block_break(1);
+            self.write_raw(line);
+        }
+        self.block_break(1);
+        self.write("```");
+        self.block_break(2);
+    }
+
+    fn block_break(&mut self, breaks: usize) {
+        if !self.after_marker {
+            self.pending_breaks = self.pending_breaks.max(breaks);
+        }
+    }
+
+    /// Writes text with whitespace sequences collapsed, like browsers render it.
+    fn write_inline(&mut self, text: &str) {
+        let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        if text.starts_with(char::is_whitespace) && !collapsed.is_empty() {
+            collapsed.insert(0, ' ');
+        }
+        if text.ends_with(char::is_whitespace) && !collapsed.ends_with(' ') {
+            collapsed.push(' ');
+        }
+
+        let at_line_start =
+            self.pending_breaks > 0 || self.out.is_empty() || self.out.ends_with('\n');
+        let collapsed = if at_line_start || self.out.ends_with(' ') {
+            collapsed.trim_start()
+        } else {
+            &collapsed
+        };
 
-    while let Some(s_idx) = html.find('<') {
-        let e_idx = html[s_idx..].find('>').unwrap_or(html.len() - 1);
-        html = html
-            .chars()
-            .take(s_idx)
-            .filter(|c| c.is_ascii())
-            .chain(html.chars().skip(s_idx + e_idx + 1))
-            .collect::<String>();
+        self.write(collapsed);
     }
 
-    html
+    fn write(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.start_line();
+        self.after_marker = false;
+        self.out.push_str(text);
+    }
+
+    /// Writes text without collapsing whitespace, used for code blocks.
+    fn write_raw(&mut self, text: &str) {
+        self.start_line();
+        self.after_marker = false;
+        self.out.push_str(text);
+    }
+
+    fn start_line(&mut self) {
+        let prefix = self.prefixes.concat();
+
+        if self.pending_breaks > 0 && !self.out.is_empty() {
+            while self.out.ends_with(' ') {
+                self.out.pop();
+            }
+            for _ in 1..self.pending_breaks {
+                self.out.push('\n');
+                self.out.push_str(prefix.trim_end());
+            }
+            self.out.push('\n');
+        }
+        self.pending_breaks = 0;
+
+        if self.out.is_empty() || self.out.ends_with('\n') {
+            self.out.push_str(&prefix);
+        }
+    }
 }
 
-pub fn extract_attribute_value(el: ElementRef, attr_name: &str) -> String {
-    el.value()
-        .attr(attr_name)
-        .map_or("".to_string(), |s| s.to_string())
+fn text_of(node: NodeRef<Node>) -> String {
+    node.descendants()
+        .filter_map(|d| match d.value() {
+            Node::Text(text) => Some(&**text),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_markdown;
+    use crate::selectors::DIV_COMMENT_CONTENT_SELECTOR;
+    use scraper::Html;
+
+    fn convert(fixture: &str) -> String {
+        let html = Html::parse_document(fixture);
+        let content = html.select(&DIV_COMMENT_CONTENT_SELECTOR).next().unwrap();
+        to_markdown(content)
+    }
+
+    #[test]
+    fn convert_code_blocks_and_links() {
+        // Act
+        let markdown = convert(include_str!("../tests/fixtures/comment_build_error.html"));
+
+        // Assert
+        assert_eq!(
+            markdown,
+            include_str!("../tests/fixtures/comment_build_error.md").trim_end()
+        );
+    }
+
+    #[test]
+    fn convert_lists_and_unicode_text() {
+        // Act
+        let markdown = convert(include_str!("../tests/fixtures/comment_unicode_list.html"));
+
+        // Assert
+        assert_eq!(
+            markdown,
+            include_str!("../tests/fixtures/comment_unicode_list.md").trim_end()
+        );
+    }
+
+    #[test]
+    fn convert_quotes_and_inline_code() {
+        // Act
+        let markdown = convert(include_str!("../tests/fixtures/comment_quote.html"));
+
+        // Assert
+        assert_eq!(
+            markdown,
+            include_str!("../tests/fixtures/comment_quote.md").trim_end()
+        );
+    }
 }
@@ -0,0 +1,364 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+};
+
+use crate::{
+    database::StoredBasicData,
+    models::{BasicPackageData, PackageData},
+};
+
+/// Packages stored by the previous run, compared with the packages list to scrape only the
+/// packages which are new or were modified since then.
+pub struct Snapshot {
+    packages: HashMap<String, StoredPackage>,
+    summary: ChangeSummary,
+}
+
+/// Package from the snapshot.
+enum StoredPackage {
+    /// Package with its details, which may be a partial record
+    Detailed(Box<PackageData>),
+    /// Package with the data from the packages list only, its details are stored but unknown
+    Basic(StoredBasicData),
+}
+
+impl StoredPackage {
+    fn basic(&self) -> &BasicPackageData {
+        match self {
+            StoredPackage::Detailed(pkg) => &pkg.basic,
+            StoredPackage::Basic(stored) => &stored.basic,
+        }
+    }
+
+    fn is_partial(&self) -> bool {
+        match self {
+            StoredPackage::Detailed(pkg) => pkg.is_partial(),
+            StoredPackage::Basic(stored) => stored.is_partial,
+        }
+    }
+
+    fn into_package_data(self) -> PackageData {
+        match self {
+            StoredPackage::Detailed(pkg) => *pkg,
+            StoredPackage::Basic(stored) => PackageData::partial(stored.basic),
+        }
+    }
+}
+
+/// Packages of the single page of the packages list split by the comparison with the snapshot.
+pub struct PageDiff {
+    /// Packages unchanged since the previous run, with the fresh data from the packages list
+    pub unchanged: Vec<(usize, PackageData)>,
+    /// New and modified packages, which have to be scraped
+    pub changed: Vec<(usize, BasicPackageData)>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChangeSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+impl Snapshot {
+    pub fn new(packages: Vec<PackageData>) -> Self {
+        Self::from_stored(packages.into_iter().map(|pkg| {
+            (
+                pkg.basic.name.clone(),
+                StoredPackage::Detailed(Box::new(pkg)),
+            )
+        }))
+    }
+
+    /// Creates the snapshot from the data from the packages list, used when details of the
+    /// stored packages are not needed. Unchanged packages of such snapshot are returned without
+    /// details, so they must not be stored again.
+    ///
+    /// Packages of every database are given, so packages missing in some of them or stored with
+    /// different data are scraped again, like partial records, and all databases catch up.
+    pub fn from_basic_data(stored_by_db: Vec<Vec<StoredBasicData>>) -> Self {
+        let databases = stored_by_db.len();
+        let mut packages: HashMap<String, (StoredBasicData, usize)> = HashMap::new();
+
+        for stored in stored_by_db.into_iter().flatten() {
+            match packages.entry(stored.basic.name.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert((stored, 1));
+                }
+                Entry::Occupied(mut entry) => {
+                    let (merged, count) = entry.get_mut();
+                    merged.is_partial |= stored.is_partial
+                        || merged.basic.version != stored.basic.version
+                        || merged.basic.last_updated != stored.basic.last_updated;
+                    *count += 1;
+                }
+            }
+        }
+
+        Self::from_stored(packages.into_iter().map(|(name, (mut stored, count))| {
+            stored.is_partial |= count < databases;
+            (name, StoredPackage::Basic(stored))
+        }))
+    }
+
+    fn from_stored(packages: impl Iterator<Item = (String, StoredPackage)>) -> Self {
+        Self {
+            packages: packages.collect(),
+            summary: ChangeSummary::default(),
+        }
+    }
+
+    /// Compares the packages listed on the page with the snapshot. Packages are modified when
+    /// their version or the last update time differ. Packages saved as partial records by the
+    /// previous run are scraped again.
+    ///
+    /// Packages list does not show the number of comments, so new comments of unchanged
+    /// packages are not detected.
+    pub fn diff(&mut self, listed: Vec<BasicPackageData>) -> PageDiff {
+        let mut diff = PageDiff {
+            unchanged: vec![],
+            changed: vec![],
+        };
+
+        for (idx, basic) in listed.into_iter().enumerate() {
+            match self.packages.remove(&basic.name) {
+                None => {
+                    self.summary.added += 1;
+                    diff.changed.push((idx, basic));
+                }
+                Some(previous)
+                    if previous.basic().version != basic.version
+                        || previous.basic().last_updated != basic.last_updated =>
+                {
+                    self.summary.updated += 1;
+                    diff.changed.push((idx, basic));
+                }
+                Some(previous) if previous.is_partial() => {
+                    self.summary.updated += 1;
+                    diff.changed.push((idx, basic));
+                }
+                Some(previous) => {
+                    self.summary.unchanged += 1;
+                    let mut previous = previous.into_package_data();
+                    previous.basic = basic;
+                    diff.unchanged.push((idx, previous));
+                }
+            }
+        }
+
+        diff
+    }
+
+    /// Returns names of the packages from the snapshot which were not listed on the compared
+    /// pages.
+    pub fn removed(&self) -> Vec<&str> {
+        let mut names = self
+            .packages
+            .keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Takes the packages out of the snapshot.
+    pub fn take(&mut self, names: &[String]) -> Vec<PackageData> {
+        names
+            .iter()
+            .filter_map(|name| self.packages.remove(name))
+            .map(StoredPackage::into_package_data)
+            .collect()
+    }
+
+    /// Returns the summary of the changes, packages not compared so far are counted as
+    /// removed.
+    pub fn summary(&self) -> ChangeSummary {
+        ChangeSummary {
+            removed: self.packages.len(),
+            ..self.summary
+        }
+    }
+}
+
+impl PageDiff {
+    /// Restores the order of the packages list, scraped packages have to be in the order of
+    /// the changed ones.
+    pub fn merge(self, scraped: Vec<PackageData>) -> Vec<PackageData> {
+        let mut packages = self.unchanged;
+        packages.extend(
+            self.changed
+                .into_iter()
+                .zip(scraped)
+                .map(|((idx, _), pkg)| (idx, pkg)),
+        );
+        packages.sort_by_key(|(idx, _)| *idx);
+        packages.into_iter().map(|(_, pkg)| pkg).collect()
+    }
+}
+
+impl fmt::Display for ChangeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} unchanged, {} removed",
+            self.added, self.updated, self.unchanged, self.removed
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChangeSummary, Snapshot};
+    use crate::{
+        database::{shared::create_package_data, StoredBasicData},
+        models::PackageData,
+    };
+    use chrono::Duration;
+
+    fn package(name: &str) -> PackageData {
+        let mut pkg = create_package_data();
+        pkg.basic.name = name.into();
+        pkg
+    }
+
+    #[test]
+    fn scrap_only_new_and_modified_packages() {
+        // Arrange
+        let mut snapshot = Snapshot::new(vec![
+            package("same"),
+            package("bumped"),
+            package("rebuilt"),
+            PackageData::partial(package("failed").basic),
+            package("deleted"),
+        ]);
+        let mut listed = vec![
+            package("new").basic,
+            package("same").basic,
+            package("bumped").basic,
+            package("rebuilt").basic,
+            package("failed").basic,
+        ];
+        listed[1].votes = 250;
+        listed[2].version = "1.3".into();
+        listed[3].last_updated += Duration::hours(1);
+
+        // Act
+        let diff = snapshot.diff(listed);
+
+        // Assert
+        let changed = diff
+            .changed
+            .iter()
+            .map(|(idx, basic)| (*idx, basic.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changed,
+            vec![(0, "new"), (2, "bumped"), (3, "rebuilt"), (4, "failed")]
+        );
+        assert_eq!(diff.unchanged.len(), 1);
+        assert_eq!(diff.unchanged[0].1.basic.votes, 250);
+        assert!(diff.unchanged[0].1.additional.is_some());
+        assert_eq!(snapshot.removed(), vec!["deleted"]);
+        assert_eq!(
+            snapshot.summary(),
+            ChangeSummary {
+                added: 1,
+                updated: 3,
+                unchanged: 1,
+                removed: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn keep_unchanged_packages_of_basic_data_snapshot() {
+        // Arrange
+        let stored = |name: &str, is_partial| StoredBasicData {
+            basic: package(name).basic,
+            is_partial,
+        };
+        let mut snapshot = Snapshot::from_basic_data(vec![vec![
+            stored("a", false),
+            stored("b", false),
+            stored("failed", true),
+        ]]);
+
+        // Act
+        let diff = snapshot.diff(vec![
+            package("a").basic,
+            package("b").basic,
+            package("failed").basic,
+        ]);
+
+        // Assert
+        let changed = diff
+            .changed
+            .iter()
+            .map(|(_, basic)| basic.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(changed, vec!["failed"]);
+        assert_eq!(diff.unchanged.len(), 2);
+        assert_eq!(
+            snapshot.summary(),
+            ChangeSummary {
+                updated: 1,
+                unchanged: 2,
+                ..ChangeSummary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn scrap_packages_missing_or_outdated_in_any_database() {
+        // Arrange
+        let stored = |name: &str| StoredBasicData {
+            basic: package(name).basic,
+            is_partial: false,
+        };
+        let mut outdated = stored("outdated");
+        outdated.basic.version = "0.9".into();
+        let mut snapshot = Snapshot::from_basic_data(vec![
+            vec![stored("same"), stored("missing"), stored("outdated")],
+            vec![stored("same"), outdated],
+        ]);
+
+        // Act
+        let diff = snapshot.diff(vec![
+            package("same").basic,
+            package("missing").basic,
+            package("outdated").basic,
+        ]);
+
+        // Assert
+        let changed = diff
+            .changed
+            .iter()
+            .map(|(_, basic)| basic.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(changed, vec!["missing", "outdated"]);
+        assert_eq!(diff.unchanged.len(), 1);
+    }
+
+    #[test]
+    fn merge_scraped_packages_in_list_order() {
+        // Arrange
+        let mut snapshot = Snapshot::new(vec![package("b")]);
+        let diff = snapshot.diff(vec![
+            package("a").basic,
+            package("b").basic,
+            package("c").basic,
+        ]);
+
+        // Act
+        let packages = diff.merge(vec![package("a"), package("c")]);
+
+        // Assert
+        let names = packages
+            .iter()
+            .map(|pkg| pkg.basic.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", "c"]);
+    }
+}
@@ -2,5 +2,10 @@ mod models;
 
 #[cfg(feature = "models")]
 pub use models::{
-    AdditionalPackageData, BasicPackageData, Comment, PackageData, PackageDependency, ModelError
+    parse_timestamp, AdditionalPackageData, BasicPackageData, Comment, ConstraintOperator,
+    DependencyKind, ModelError, PackageData, PackageDependency, PackageRelation, PackageVersion,
+    Source, VersionConstraint,
 };
+
+#[cfg(feature = "models")]
+pub use chrono::{DateTime, Utc};
@@ -1,29 +1,50 @@
+mod archive;
+mod checkpoint;
 mod cli;
 mod database;
 mod html;
+mod incremental;
 mod models;
+mod rate_limit;
+mod removal;
+mod retry;
+mod rpc;
 mod scrap;
 mod selectors;
 mod serialization;
+#[cfg(test)]
+mod stub_server;
 
+use checkpoint::Checkpoint;
 use clap::Parser;
-use cli::{Cli, Commands, FromFsArgs, ToFsArgs};
-use database::{DatabasePackageIO, RedisIO, SkytableIO, SurrealIO};
-use serialization::{read_binary_file_and_deserialize, save_to_binary_file, serialize_to_bson};
+use cli::{
+    ArchiveArgs, Cli, Commands, DataSource, FromFsArgs, PackagesArgs, SearchArgs, ToDbArgs,
+    ToFsArgs,
+};
+use database::DatabasePackageIO;
+use incremental::{PageDiff, Snapshot};
+use models::{BasicPackageData, PackageData};
+use removal::{ListedPackages, REMOVED_FILE_NAME};
+use serialization::{
+    read_binary_file_and_deserialize, read_pages_from_dir, save_to_binary_file, serialize_to_bson,
+};
+use std::collections::HashSet;
 use std::fs::File;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::time::Instant;
-use tracing::{error, info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-use crate::scrap::{get_page_and_scrap_packages, AurScraper, AUR_BASE_URL, AUR_PAGE_QUERY};
+use crate::{
+    rpc::{AurRpcSource, AUR_URL},
+    scrap::{scrap_packages, AurScraper, PackagesListPage, PackagesListQuery, PACKAGES_PER_PAGE},
+};
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    let scraper = Arc::new(AurScraper::new());
-
     let log_file = File::create("logs/errors.log").unwrap();
 
     tracing_subscriber::registry()
@@ -36,78 +57,700 @@ async fn main() {
         .init();
 
     match &cli.command {
-        Commands::ScrapToFs(fs_args) => scrap_and_save_to_fs(scraper, fs_args).await,
-        Commands::ScrapToDb(_db_args) => todo!(),
+        Commands::ScrapToFs(fs_args) => {
+            let scraper = Arc::new(AurScraper::new(fs_args.scraper.to_config()));
+            scrap_and_save_to_fs(scraper, fs_args).await
+        }
+        Commands::ScrapToDb(db_args) => {
+            let scraper = Arc::new(AurScraper::new(db_args.scraper.to_config()));
+            scrap_and_save_to_db(scraper, db_args).await
+        }
         Commands::LoadFromFs(from_fs_args) => {
             load_from_file_system_to_databases(from_fs_args).await
         }
+        Commands::ImportArchive(archive_args) => {
+            let scraper = Arc::new(AurScraper::new(archive_args.scraper.to_config()));
+            import_archive(scraper, archive_args).await
+        }
+        Commands::ScrapPackages(packages_args) => {
+            let scraper = Arc::new(AurScraper::new(packages_args.scraper.to_config()));
+            scrap_named_packages(scraper, packages_args).await
+        }
+        Commands::SearchPackages(search_args) => {
+            let scraper = Arc::new(AurScraper::new(search_args.scraper.to_config()));
+            search_packages(scraper, search_args).await
+        }
     }
 }
 
 async fn scrap_and_save_to_fs(scraper: Arc<AurScraper>, cfg: &ToFsArgs) {
-    let start_page = cfg.start_page - 1;
-    let end_page = cfg.end_page.unwrap_or(cfg.start_page);
-    let pages_range = start_page..(end_page);
     let start = Instant::now();
 
-    for i in pages_range {
-        let url = format!("{}{}{}", AUR_BASE_URL, AUR_PAGE_QUERY, i * 250);
+    let checkpoint_path = Checkpoint::path(&cfg.path);
+    let mut checkpoint = if cfg.resume {
+        match Checkpoint::load(&checkpoint_path) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                error!("Cannot resume scraping. Caused by: {:#}", e);
+                return;
+            }
+        }
+    } else {
+        Checkpoint::default()
+    };
+
+    let mut snapshot = match &cfg.previous {
+        Some(dir) => match read_pages_from_dir(dir) {
+            Ok(packages) => Some(Snapshot::new(packages)),
+            Err(e) => {
+                error!("Cannot read previous run from {}. Caused by: {:#}", dir, e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut listed = ListedPackages::default();
+    listed.record_pages_count(checkpoint.pages_count);
+    let query = cfg.list.to_query();
+    // All pages are scraped by default, their number is read from the first scraped page
+    let mut end_page = cfg.end_page.or(checkpoint.pages_count);
+    let mut scraped_pages = 0;
 
-        match get_page_and_scrap_packages(scraper.clone(), &url).await {
+    for page in cfg.start_page.. {
+        if end_page.is_some_and(|end| page > end) {
+            break;
+        }
+        scraped_pages += 1;
+        let file_name = format!("{}/page_{}.bson", cfg.path, page);
+
+        let packages = if checkpoint.is_completed(page) {
+            if checkpoint.failed_packages(page).is_empty() {
+                info!("Skipping page {} scraped in the previous run", page);
+                listed.record_missing_page();
+                continue;
+            }
+            retry_partial_records(scraper.clone(), cfg.scraper.source, &file_name).await
+        } else {
+            let url = query.page_url(page);
+            let list_page = match get_packages_list_page(&scraper, &url, page, end_page).await {
+                Ok(list_page) => list_page,
+                Err(e) => {
+                    listed.record_missing_page();
+                    error!("{}", e);
+                    if end_page.is_none() {
+                        error!("Number of pages is unknown, stopping at page {}", page);
+                        break;
+                    }
+                    continue;
+                }
+            };
+            end_page = list_page.last_page(page, end_page);
+            listed.record_pages_count(list_page.pages_count);
+            if list_page.pages_count.is_some() {
+                checkpoint.pages_count = list_page.pages_count;
+            }
+
+            match &mut snapshot {
+                Some(snapshot) => scrap_changed_packages(
+                    scraper.clone(),
+                    cfg.scraper.source,
+                    &url,
+                    list_page.packages,
+                    snapshot,
+                )
+                .await
+                .map(|(diff, scraped)| diff.merge(scraped)),
+                None => {
+                    scrap_page(
+                        scraper.clone(),
+                        cfg.scraper.source,
+                        &url,
+                        list_page.packages,
+                    )
+                    .await
+                }
+            }
+        };
+
+        match packages {
             Ok(packages) => {
-                let file_name = format!("{}/page_{}.bson", cfg.path, i + 1);
-                let serialized = serialize_to_bson(packages).unwrap();
+                let serialized = serialize_to_bson(&packages).unwrap();
                 save_to_binary_file(&file_name, &serialized).await.unwrap();
+
+                checkpoint.record_page(page, &packages);
+                if let Err(e) = checkpoint.save(&checkpoint_path).await {
+                    error!("Cannot save checkpoint. Caused by: {:#}", e);
+                }
+
+                listed.record_page(page, package_names(&packages));
+            }
+            Err(e) => {
+                listed.record_missing_page();
+                error!("{}", e)
             }
-            Err(e) => error!("{}", e),
         }
     }
 
     let duration = start.elapsed();
     info!(
         "Scraped {} pages of packages in {:?}",
-        end_page - start_page,
-        duration
+        scraped_pages, duration
+    );
+    info!("Scraper {}", scraper.attempts_summary());
+
+    if let (Some(snapshot), Some(previous_dir)) = (&mut snapshot, &cfg.previous) {
+        report_changes(snapshot);
+
+        if query.is_filtered() {
+            info!("Packages list is filtered, removed packages are not detected");
+        } else if listed.is_complete() {
+            let rpc = AurRpcSource::new(scraper.clone(), AUR_URL);
+            let result =
+                removal::save_removed_to_fs(&rpc, snapshot, &listed, previous_dir, &cfg.path).await;
+            if let Err(e) = result {
+                error!("Cannot save removed packages. Caused by: {:#}", e);
+            }
+        } else {
+            info!("Not all pages were scraped, removed packages are not detected");
+        }
+    }
+
+    let failed = checkpoint
+        .failed_packages
+        .values()
+        .map(Vec::len)
+        .sum::<usize>();
+    if failed > 0 {
+        info!(
+            "{} packages saved as partial records, run with --resume to scrap them again",
+            failed
+        );
+    }
+}
+
+/// Scrapes again packages saved as partial records to the BSON file.
+async fn retry_partial_records(
+    scraper: Arc<AurScraper>,
+    source: DataSource,
+    file_name: &str,
+) -> anyhow::Result<Vec<PackageData>> {
+    let mut packages = read_binary_file_and_deserialize(file_name)?;
+    let partial = packages
+        .iter()
+        .filter(|pkg| pkg.is_partial())
+        .map(|pkg| pkg.basic.clone())
+        .collect::<Vec<_>>();
+
+    info!(
+        "Retrying {} partial records of {}",
+        partial.len(),
+        file_name
+    );
+
+    let mut retried = scrap_packages_from(scraper, source, partial)
+        .await?
+        .into_iter();
+    for pkg in packages.iter_mut().filter(|pkg| pkg.is_partial()) {
+        if let Some(retried) = retried.next() {
+            *pkg = retried;
+        }
+    }
+
+    Ok(packages)
+}
+
+async fn scrap_and_save_to_db(scraper: Arc<AurScraper>, cfg: &ToDbArgs) {
+    let mut databases = vec![];
+
+    for cs in &cfg.cs {
+        match database::connect(cs).await {
+            Ok(db) => databases.push(db),
+            Err(e) => {
+                error!("Cannot connect to database {}. Caused by: {:#}", cs, e);
+                return;
+            }
+        }
+    }
+
+    // Packages stored in all databases are compared with the packages list
+    let mut snapshot = None;
+    if cfg.incremental {
+        let mut stored_by_db = vec![];
+        for db in &databases {
+            match db.get_all_basic_data().await {
+                Ok(packages) => stored_by_db.push(packages),
+                Err(e) => {
+                    error!(
+                        "Cannot read packages from {} database. Caused by: {:#}",
+                        db.name(),
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+        snapshot = Some(Snapshot::from_basic_data(stored_by_db));
+    }
+
+    let start = Instant::now();
+    let mut listed = ListedPackages::default();
+    let query = cfg.list.to_query();
+    // All pages are scraped by default, their number is read from the first scraped page
+    let mut end_page = cfg.end_page;
+    let mut scraped_pages = 0;
+
+    for page in cfg.start_page.. {
+        if end_page.is_some_and(|end| page > end) {
+            break;
+        }
+        scraped_pages += 1;
+        let url = query.page_url(page);
+
+        let list_page = match get_packages_list_page(&scraper, &url, page, end_page).await {
+            Ok(list_page) => list_page,
+            Err(e) => {
+                listed.record_missing_page();
+                error!("{}", e);
+                if end_page.is_none() {
+                    error!("Number of pages is unknown, stopping at page {}", page);
+                    break;
+                }
+                continue;
+            }
+        };
+        end_page = list_page.last_page(page, end_page);
+        listed.record_pages_count(list_page.pages_count);
+
+        let packages = match &mut snapshot {
+            Some(snapshot) => scrap_changed_packages(
+                scraper.clone(),
+                cfg.scraper.source,
+                &url,
+                list_page.packages,
+                snapshot,
+            )
+            .await
+            .map(|(diff, scraped)| {
+                let mut names = package_names(&scraped);
+                names.extend(diff.unchanged.iter().map(|(_, pkg)| pkg.basic.name.clone()));
+                listed.record_page(page, names);
+                scraped
+            }),
+            None => scrap_page(
+                scraper.clone(),
+                cfg.scraper.source,
+                &url,
+                list_page.packages,
+            )
+            .await
+            .inspect(|packages| listed.record_page(page, package_names(packages))),
+        };
+
+        match packages {
+            Ok(packages) => insert_into_databases(&databases, &packages).await,
+            Err(e) => {
+                listed.record_missing_page();
+                error!("{}", e)
+            }
+        }
+    }
+
+    let duration = start.elapsed();
+    info!(
+        "Scraped {} pages of packages to databases in {:?}",
+        scraped_pages, duration
+    );
+    info!("Scraper {}", scraper.attempts_summary());
+    if let Some(snapshot) = &snapshot {
+        report_changes(snapshot);
+    }
+
+    if query.is_filtered() {
+        info!("Packages list is filtered, removed packages are not detected");
+    } else if listed.is_complete() {
+        let rpc = AurRpcSource::new(scraper.clone(), AUR_URL);
+        if let Err(e) = removal::mark_removed_in_databases(&rpc, &databases, &listed).await {
+            error!("Cannot mark removed packages. Caused by: {:#}", e);
+        }
+    } else {
+        info!("Not all pages were scraped, removed packages are not detected");
+    }
+}
+
+async fn import_archive(scraper: Arc<AurScraper>, cfg: &ArchiveArgs) {
+    let mut databases = vec![];
+
+    for cs in &cfg.cs {
+        match database::connect(cs).await {
+            Ok(db) => databases.push(db),
+            Err(e) => {
+                error!("Cannot connect to database {}. Caused by: {:#}", cs, e);
+                return;
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let mut packages = match archive::read_meta_archive(&cfg.archive).await {
+        Ok(packages) => packages,
+        Err(e) => {
+            error!("Cannot read archive {}. Caused by: {:#}", cfg.archive, e);
+            return;
+        }
+    };
+    info!("Read {} packages from the archive", packages.len());
+
+    let rpc = AurRpcSource::new(scraper.clone(), AUR_URL);
+
+    // Packages are saved in pages, so comments scraped so far are kept if the run is stopped
+    for (i, page) in packages.chunks_mut(PACKAGES_PER_PAGE).enumerate() {
+        if cfg.with_comments {
+            rpc.fetch_comments(page).await;
+        }
+
+        if let Some(path) = &cfg.path {
+            let file_name = format!("{}/page_{}.bson", path, i + 1);
+            let serialized = serialize_to_bson(page).unwrap();
+            save_to_binary_file(&file_name, &serialized).await.unwrap();
+        }
+
+        insert_into_databases(&databases, page).await;
+    }
+
+    let duration = start.elapsed();
+    info!("Imported {} packages in {:?}", packages.len(), duration);
+    if cfg.with_comments {
+        info!("Scraper {}", scraper.attempts_summary());
+    }
+}
+
+async fn scrap_named_packages(scraper: Arc<AurScraper>, cfg: &PackagesArgs) {
+    let mut databases = vec![];
+
+    for cs in &cfg.cs {
+        match database::connect(cs).await {
+            Ok(db) => databases.push(db),
+            Err(e) => {
+                error!("Cannot connect to database {}. Caused by: {:#}", cs, e);
+                return;
+            }
+        }
+    }
+
+    let names = match read_package_names(cfg) {
+        Ok(names) => names,
+        Err(e) => {
+            error!("Cannot read package names. Caused by: {:#}", e);
+            return;
+        }
+    };
+
+    let start = Instant::now();
+    let mut packages_basic_data = vec![];
+
+    for name in &names {
+        match get_package_basic_data(&scraper, name).await {
+            Ok(Some(basic)) => packages_basic_data.push(basic),
+            Ok(None) => error!("Package {} not found in the AUR", name),
+            Err(e) => error!("Cannot look up package {}. Caused by: {:#}", name, e),
+        }
+    }
+
+    let packages =
+        match scrap_packages_from(scraper.clone(), cfg.scraper.source, packages_basic_data).await {
+            Ok(packages) => packages,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
+
+    for (i, page) in packages.chunks(PACKAGES_PER_PAGE).enumerate() {
+        if let Some(path) = &cfg.path {
+            let file_name = format!("{}/page_{}.bson", path, i + 1);
+            let serialized = serialize_to_bson(page).unwrap();
+            save_to_binary_file(&file_name, &serialized).await.unwrap();
+        }
+
+        insert_into_databases(&databases, page).await;
+    }
+
+    let duration = start.elapsed();
+    let partial = packages.iter().filter(|pkg| pkg.is_partial()).count();
+    info!(
+        "Scraped {} of {} packages in {:?} ({} partial records)",
+        packages.len(),
+        names.len(),
+        duration,
+        partial
+    );
+    info!("Scraper {}", scraper.attempts_summary());
+}
+
+/// Searches packages with the RPC, the found packages are saved in pages like by other commands.
+async fn search_packages(scraper: Arc<AurScraper>, cfg: &SearchArgs) {
+    let mut databases = vec![];
+
+    for cs in &cfg.cs {
+        match database::connect(cs).await {
+            Ok(db) => databases.push(db),
+            Err(e) => {
+                error!("Cannot connect to database {}. Caused by: {:#}", cs, e);
+                return;
+            }
+        }
+    }
+
+    let start = Instant::now();
+    let mut rpc = AurRpcSource::new(scraper.clone(), AUR_URL);
+    if !cfg.with_comments {
+        rpc = rpc.without_comments();
+    }
+
+    let packages = match rpc.search(&cfg.keywords, cfg.by).await {
+        Ok(packages) => packages,
+        Err(e) => {
+            error!("Cannot search packages. Caused by: {:#}", e);
+            return;
+        }
+    };
+
+    for (i, page) in packages.chunks(PACKAGES_PER_PAGE).enumerate() {
+        if let Some(path) = &cfg.path {
+            let file_name = format!("{}/page_{}.bson", path, i + 1);
+            let serialized = serialize_to_bson(page).unwrap();
+            save_to_binary_file(&file_name, &serialized).await.unwrap();
+        }
+
+        insert_into_databases(&databases, page).await;
+    }
+
+    let duration = start.elapsed();
+    info!("Found {} packages in {:?}", packages.len(), duration);
+    if cfg.with_comments {
+        info!("Scraper {}", scraper.attempts_summary());
+    }
+}
+
+/// Collects package names passed as arguments and listed in the file, without duplicates.
+fn read_package_names(cfg: &PackagesArgs) -> anyhow::Result<Vec<String>> {
+    let mut names = cfg.names.clone();
+
+    if let Some(file) = &cfg.file {
+        let content = std::fs::read_to_string(file)?;
+        names.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(name.clone()));
+
+    Ok(names)
+}
+
+/// Looks the package up on the packages list by its exact name, `None` is returned when the AUR
+/// has no such package.
+async fn get_package_basic_data(
+    scraper: &AurScraper,
+    name: &str,
+) -> anyhow::Result<Option<BasicPackageData>> {
+    let url = PackagesListQuery::exact_name(name).page_url(1);
+    let list_page = scraper.get_packages_list_page(&url).await?;
+
+    Ok(list_page.packages.into_iter().find(|pkg| pkg.name == name))
+}
+
+/// Fetches the page of the packages list, the number of pages is logged when it is read from
+/// the page for the first time.
+async fn get_packages_list_page(
+    scraper: &AurScraper,
+    url: &str,
+    page: u32,
+    end_page: Option<u32>,
+) -> anyhow::Result<PackagesListPage> {
+    let list_page = scraper.get_packages_list_page(url).await?;
+
+    if let (None, Some(pages_count)) = (end_page, list_page.pages_count) {
+        match list_page.packages_count {
+            Some(count) => info!("Found {} packages on {} pages", count, pages_count),
+            None => info!("Found {} pages of packages", pages_count),
+        }
+    }
+    if list_page.is_last() {
+        info!("Page {} is the last page of the packages list", page);
+    }
+
+    Ok(list_page)
+}
+
+/// Scrapes packages listed on the page of the packages list.
+async fn scrap_page(
+    scraper: Arc<AurScraper>,
+    source: DataSource,
+    url: &str,
+    packages_basic_data: Vec<BasicPackageData>,
+) -> anyhow::Result<Vec<PackageData>> {
+    let start = Instant::now();
+    let packages = scrap_packages_from(scraper, source, packages_basic_data).await?;
+
+    let duration = start.elapsed();
+    let partial = packages.iter().filter(|pkg| pkg.is_partial()).count();
+    info!(
+        "Scraped packages from {} in: {:?} ({} partial records)",
+        url, duration, partial
     );
+
+    Ok(packages)
+}
+
+fn package_names(packages: &[PackageData]) -> Vec<String> {
+    packages.iter().map(|pkg| pkg.basic.name.clone()).collect()
+}
+
+fn report_changes(snapshot: &Snapshot) {
+    info!("Changes since the previous run: {}", snapshot.summary());
+
+    for name in snapshot.removed() {
+        info!(
+            "Package {} is not listed on the scraped pages anymore",
+            name
+        );
+    }
+}
+
+/// Scrapes packages of the page which are new or were modified since the snapshot was taken.
+async fn scrap_changed_packages(
+    scraper: Arc<AurScraper>,
+    source: DataSource,
+    url: &str,
+    listed: Vec<BasicPackageData>,
+    snapshot: &mut Snapshot,
+) -> anyhow::Result<(PageDiff, Vec<PackageData>)> {
+    let diff = snapshot.diff(listed);
+    let changed = diff
+        .changed
+        .iter()
+        .map(|(_, basic)| basic.clone())
+        .collect::<Vec<_>>();
+
+    info!(
+        "Scraping {} new or modified packages from {}",
+        changed.len(),
+        url
+    );
+    let scraped = scrap_packages_from(scraper, source, changed).await?;
+
+    Ok((diff, scraped))
+}
+
+async fn scrap_packages_from(
+    scraper: Arc<AurScraper>,
+    source: DataSource,
+    packages_basic_data: Vec<BasicPackageData>,
+) -> anyhow::Result<Vec<PackageData>> {
+    match source {
+        DataSource::Html => Ok(scrap_packages(scraper, packages_basic_data).await),
+        DataSource::Rpc => {
+            AurRpcSource::new(scraper, AUR_URL)
+                .get_packages(packages_basic_data)
+                .await
+        }
+    }
+}
+
+async fn insert_into_databases(databases: &[Box<dyn DatabasePackageIO>], packages: &[PackageData]) {
+    for pkg in packages {
+        let mut failed = 0;
+        for db in databases {
+            if let Err(e) = db.insert(pkg).await {
+                failed += 1;
+                error!(
+                    "Failed to insert {} to {} database. Caused by: {}",
+                    &pkg.basic.name,
+                    db.name(),
+                    e
+                );
+            }
+        }
+
+        if failed == 0 {
+            info!("Loaded {} package to all databases", &pkg.basic.name);
+        } else {
+            warn!(
+                "Loaded {} package to {} of {} databases",
+                &pkg.basic.name,
+                databases.len() - failed,
+                databases.len()
+            );
+        }
+    }
 }
 
 async fn load_from_file_system_to_databases(cfg: &FromFsArgs) {
+    let mut databases = vec![];
+
+    for cs in cfg.connection_strings() {
+        match database::connect(cs).await {
+            Ok(db) => databases.push(db),
+            Err(e) => {
+                error!("Cannot connect to database {}. Caused by: {:#}", cs, e);
+                return;
+            }
+        }
+    }
+
     let start_page = cfg.start_page;
     let end_page = cfg.end_page.unwrap_or(cfg.start_page) + 1;
     let pages_range = start_page..(end_page);
 
     for i in pages_range {
         let file_path = format!("{}/page_{}.bson", cfg.path, i);
-        let packages = read_binary_file_and_deserialize(&file_path)
-            .expect(&format!("Cannot read and deserialize file {}", file_path));
 
-        let redis = RedisIO::try_new().expect("Cannot create RedisIO");
-        let surreal = SurrealIO::try_new().await.expect("Cannot create SurrealIO");
-        let skytable = SkytableIO::try_new().expect("Cannot create SkytableIO");
-        skytable.create_tables().expect("Cannot create tables in Skytable");
+        match read_binary_file_and_deserialize(&file_path) {
+            Ok(packages) => insert_into_databases(&databases, &packages).await,
+            Err(e) => error!(
+                "Cannot read and deserialize file {}. Caused by: {}",
+                file_path, e
+            ),
+        }
+    }
+
+    let removed_path = Path::new(&cfg.path).join(REMOVED_FILE_NAME);
+    if removed_path.exists() {
+        match read_binary_file_and_deserialize(&removed_path.to_string_lossy()) {
+            Ok(tombstones) => load_tombstones(&databases, &tombstones).await,
+            Err(e) => error!(
+                "Cannot read and deserialize file {}. Caused by: {}",
+                removed_path.display(),
+                e
+            ),
+        }
+    }
+}
 
-        for pkg in packages {
-            if let Err(e) = redis.insert(&pkg).await {
-                error!(
-                    "Failed to insert {} to Redis database. Caused by: {}",
-                    &pkg.basic.name, e
-                );
-            }
+async fn load_tombstones(databases: &[Box<dyn DatabasePackageIO>], tombstones: &[PackageData]) {
+    insert_into_databases(databases, tombstones).await;
 
-            if let Err(e) = skytable.insert(&pkg).await {
-                error!(
-                    "Failed to insert {} to Skytable database. Caused by: {}",
-                    &pkg.basic.name, e
-                );
-            }
+    for pkg in tombstones {
+        let Some(removed_at) = pkg.removed_at else {
+            continue;
+        };
 
-            if let Err(e) = surreal.insert(&pkg).await {
+        for db in databases {
+            if let Err(e) = db.mark_removed(&pkg.basic.name, removed_at).await {
                 error!(
-                    "Failed to insert {} to Surreal database. Caused by: {}",
-                    &pkg.basic.name, e
+                    "Failed to mark {} as removed in {} database. Caused by: {}",
+                    &pkg.basic.name,
+                    db.name(),
+                    e
                 );
             }
-            info!("Loaded {} package to all databases", &pkg.basic.name);
         }
     }
 }
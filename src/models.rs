@@ -1,17 +1,48 @@
 use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Ordering,
     collections::HashMap,
+    fmt::{self, Display},
     num::{ParseFloatError, ParseIntError},
+    str::FromStr,
 };
 use thiserror::Error;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PackageData {
     pub basic: BasicPackageData,
-    pub additional: AdditionalPackageData,
+    /// Data from the package details page, `None` when the page could not be scraped
+    pub additional: Option<AdditionalPackageData>,
     pub dependencies: Vec<PackageDependency>,
+    /// Names of the packages depending on this one
+    #[serde(default)]
+    pub required_by: Vec<String>,
     pub comments: Vec<Comment>,
+    /// Time when the package was found deleted from the AUR, the record is kept as a tombstone
+    #[serde(default, with = "timestamp::option")]
+    pub removed_at: Option<DateTime<Utc>>,
+}
+
+impl PackageData {
+    /// Creates a record holding only the data from the packages list, used when scraping of
+    /// the package details page failed.
+    pub fn partial(basic: BasicPackageData) -> Self {
+        Self {
+            basic,
+            additional: None,
+            dependencies: vec![],
+            required_by: vec![],
+            comments: vec![],
+            removed_at: None,
+        }
+    }
+
+    /// Returns true when the package details could not be scraped.
+    pub fn is_partial(&self) -> bool {
+        self.additional.is_none()
+    }
 }
 
 impl TryFrom<HashMap<String, String>> for PackageData {
@@ -38,7 +69,7 @@ impl TryFrom<HashMap<String, String>> for PackageData {
             })?;
         let description = getter("description")?;
         let maintainer = getter("maintainer")?;
-        let last_updated = getter("last_updated")?;
+        let last_updated = parse_timestamp(&getter("last_updated")?, "last_updated")?;
 
         let basic = BasicPackageData {
             name,
@@ -51,13 +82,27 @@ impl TryFrom<HashMap<String, String>> for PackageData {
             last_updated,
         };
 
-        let additional = AdditionalPackageData::try_from(source)?;
+        let removed_at = get_optional_field(&mut source, "removed_at")
+            .map(|t| parse_timestamp(&t, "removed_at"))
+            .transpose()?;
+
+        // Votes and popularity shown on the details page share the fields with the basic data
+        source.insert("votes".into(), basic.votes.to_string());
+        source.insert("popularity".into(), basic.popularity.to_string());
+
+        let additional = if source.contains_key("gitcloneurl") {
+            Some(AdditionalPackageData::try_from(source)?)
+        } else {
+            None
+        };
 
         Ok(Self {
             basic,
             additional,
             comments: vec![],
             dependencies: vec![],
+            required_by: vec![],
+            removed_at,
         })
     }
 }
@@ -71,7 +116,32 @@ fn get_obligatory_field(
         .ok_or(ModelError::MissingSourceData { field: key })
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn get_optional_field(source: &mut HashMap<String, String>, key: &'static str) -> Option<String> {
+    source.remove(key).filter(|v| !v.is_empty())
+}
+
+fn get_relations_field(
+    source: &mut HashMap<String, String>,
+    key: &'static str,
+) -> Result<Vec<PackageRelation>, ModelError> {
+    get_list_field(source, key)
+        .iter()
+        .map(|relation| relation.parse())
+        .collect()
+}
+
+fn get_list_field(source: &mut HashMap<String, String>, key: &'static str) -> Vec<String> {
+    get_optional_field(source, key)
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BasicPackageData {
     pub name: String,
     pub version: String,
@@ -79,9 +149,17 @@ pub struct BasicPackageData {
     pub votes: i32,
     pub popularity: f32,
     pub description: String,
+    /// Empty for orphaned packages
     pub maintainer: String,
-    // TODO: use chrono
-    pub last_updated: String,
+    #[serde(with = "timestamp")]
+    pub last_updated: DateTime<Utc>,
+}
+
+impl BasicPackageData {
+    /// Returns the version parsed for comparisons, e.g. to detect upgrades between snapshots.
+    pub fn package_version(&self) -> Result<PackageVersion, ModelError> {
+        self.version.parse()
+    }
 }
 
 impl TryFrom<Vec<String>> for BasicPackageData {
@@ -136,6 +214,7 @@ impl TryFrom<Vec<String>> for BasicPackageData {
         let last_updated = iter.next().ok_or(ModelError::MissingSourceData {
             field: "last_updated",
         })?;
+        let last_updated = parse_displayed_timestamp(&last_updated, "last_updated")?;
 
         Ok(BasicPackageData {
             name,
@@ -153,28 +232,73 @@ impl TryFrom<Vec<String>> for BasicPackageData {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AdditionalPackageData {
     pub git_clone_url: String,
-    pub keywords: Option<String>,
-    pub license: Option<String>,
-    pub confilcts: Option<String>,
-    pub provides: Option<String>,
+    #[serde(default, deserialize_with = "joined_list::deserialize")]
+    pub keywords: Vec<String>,
+    #[serde(
+        default,
+        alias = "license",
+        deserialize_with = "joined_list::deserialize"
+    )]
+    pub licenses: Vec<String>,
+    #[serde(
+        default,
+        alias = "confilcts",
+        deserialize_with = "joined_list::deserialize"
+    )]
+    pub conflicts: Vec<PackageRelation>,
+    #[serde(default, deserialize_with = "joined_list::deserialize")]
+    pub provides: Vec<PackageRelation>,
     pub submitter: String,
-    // TODO: use chrono
-    pub first_submitted: String,
+    #[serde(with = "timestamp")]
+    pub first_submitted: DateTime<Utc>,
+    /// Base the package is built from, it differs from the package name for split packages
+    #[serde(default)]
+    pub package_base: Option<String>,
+    /// Other packages built from the same package base
+    #[serde(default)]
+    pub split_packages: Vec<String>,
+    #[serde(default)]
+    pub sources: Vec<Source>,
+    #[serde(default)]
+    pub upstream_url: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub replaces: Vec<String>,
+    #[serde(default)]
+    pub co_maintainers: Vec<String>,
+    #[serde(default)]
+    pub last_packager: Option<String>,
+    /// Date when the package was flagged out-of-date, `None` when it is up to date
+    #[serde(default, with = "timestamp::option")]
+    pub out_of_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub votes: Option<i32>,
+    #[serde(default)]
+    pub popularity: Option<f32>,
 }
 
 impl TryFrom<HashMap<String, String>> for AdditionalPackageData {
     type Error = ModelError;
 
     fn try_from(mut source: HashMap<String, String>) -> Result<Self, Self::Error> {
+        Self::from_fields(&mut source)
+    }
+}
+
+impl AdditionalPackageData {
+    /// Builds the data from the fields of the `table#pkginfo` table keyed by their lowercase
+    /// labels. Consumed fields are removed from the map, so the remaining ones are unknown.
+    pub fn from_fields(source: &mut HashMap<String, String>) -> Result<Self, ModelError> {
         let git_clone_url = source
             .remove("gitcloneurl")
             .ok_or(ModelError::MissingSourceData {
                 field: "git_clone_url",
             })?;
-        let keywords = source.remove("keywords");
-        let license = source.remove("licenses");
-        let confilcts = source.remove("conflicts");
-        let provides = source.remove("provides");
+        let keywords = get_list_field(source, "keywords");
+        let licenses = get_list_field(source, "licenses");
+        let conflicts = get_relations_field(source, "conflicts")?;
+        let provides = get_relations_field(source, "provides")?;
         let submitter = source
             .remove("submitter")
             .ok_or(ModelError::MissingSourceData { field: "submitter" })?;
@@ -184,29 +308,363 @@ impl TryFrom<HashMap<String, String>> for AdditionalPackageData {
                 .ok_or(ModelError::MissingSourceData {
                     field: "first_submitted",
                 })?;
+        let first_submitted = parse_timestamp(&first_submitted, "first_submitted")?;
+        let package_base = get_optional_field(source, "packagebase");
+        let upstream_url = get_optional_field(source, "upstreamurl");
+        let groups = get_list_field(source, "groups");
+        let replaces = get_list_field(source, "replaces");
+        let co_maintainers = get_list_field(source, "comaintainers");
+        let last_packager = get_optional_field(source, "lastpackager");
+        let out_of_date = get_optional_field(source, "outofdate")
+            .map(|t| parse_timestamp(&t, "out_of_date"))
+            .transpose()?;
+        let votes = get_optional_field(source, "votes")
+            .map(|v| v.parse())
+            .transpose()
+            .map_err(|e: ParseIntError| ModelError::ParseError {
+                field: "votes",
+                source: anyhow!(e),
+            })?;
+        let popularity = get_optional_field(source, "popularity")
+            .map(|p| p.parse())
+            .transpose()
+            .map_err(|e: ParseFloatError| ModelError::ParseError {
+                field: "popularity",
+                source: anyhow!(e),
+            })?;
 
         Ok(Self {
             git_clone_url,
             keywords,
-            license,
-            confilcts,
+            licenses,
+            conflicts,
             provides,
             submitter,
             first_submitted,
+            package_base,
+            split_packages: vec![],
+            sources: vec![],
+            upstream_url,
+            groups,
+            replaces,
+            co_maintainers,
+            last_packager,
+            out_of_date,
+            votes,
+            popularity,
+        })
+    }
+}
+
+/// Package referenced by the `conflicts` or `provides` array of the PKGBUILD, e.g. `java-runtime=17`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageRelation {
+    pub name: String,
+    pub constraint: Option<VersionConstraint>,
+}
+
+impl FromStr for PackageRelation {
+    type Err = ModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, constraint) = VersionConstraint::split(s);
+
+        if name.is_empty() {
+            return Err(ModelError::ParseError {
+                field: "name",
+                source: anyhow!("Package name is missing in {}", s),
+            });
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            constraint,
+        })
+    }
+}
+
+impl Display for PackageRelation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.constraint {
+            Some(constraint) => write!(f, "{}{}", self.name, constraint),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// File listed in the `source` array of the PKGBUILD.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Source {
+    pub filename: String,
+    pub url: String,
+}
+
+impl TryFrom<HashMap<String, String>> for Source {
+    type Error = ModelError;
+
+    fn try_from(mut source: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let mut getter = |k| get_obligatory_field(&mut source, k);
+
+        Ok(Self {
+            filename: getter("filename")?,
+            url: getter("url")?,
         })
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "PackageDependencyRepr")]
 pub struct PackageDependency {
-    pub group: String,
-    pub packages: Vec<String>,
+    pub name: String,
+    pub kind: DependencyKind,
+    pub constraint: Option<VersionConstraint>,
+    /// Reason given for optional dependencies, e.g. "for the GUI"
+    pub description: Option<String>,
+    /// True when the dependency is an AUR package, false when it comes from the official repos,
+    /// `None` when the origin is unknown
+    pub is_aur: Option<bool>,
+    /// Packages providing the dependency when it is a virtual one
+    pub providers: Vec<String>,
+}
+
+/// Accepts dependencies stored before they were typed, when only the dependency name was kept
+/// in `group` and its providers in `packages`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PackageDependencyRepr {
+    Typed {
+        name: String,
+        kind: DependencyKind,
+        constraint: Option<VersionConstraint>,
+        description: Option<String>,
+        is_aur: Option<bool>,
+        #[serde(default)]
+        providers: Vec<String>,
+    },
+    Legacy {
+        group: String,
+        packages: Vec<String>,
+    },
+}
+
+impl From<PackageDependencyRepr> for PackageDependency {
+    fn from(repr: PackageDependencyRepr) -> Self {
+        match repr {
+            PackageDependencyRepr::Typed {
+                name,
+                kind,
+                constraint,
+                description,
+                is_aur,
+                providers,
+            } => Self {
+                name,
+                kind,
+                constraint,
+                description,
+                is_aur,
+                providers,
+            },
+            PackageDependencyRepr::Legacy { group, packages } => {
+                let (name, constraint) = VersionConstraint::split(&group);
+                Self {
+                    name: name.to_string(),
+                    kind: DependencyKind::Depends,
+                    constraint,
+                    description: None,
+                    is_aur: None,
+                    providers: packages,
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<HashMap<String, String>> for PackageDependency {
+    type Error = ModelError;
+
+    fn try_from(mut source: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let mut getter = |k| get_obligatory_field(&mut source, k);
+
+        let name = getter("name")?;
+        let kind = getter("kind")?.parse()?;
+        let constraint = get_optional_field(&mut source, "constraint")
+            .map(|c| c.parse())
+            .transpose()?;
+        let description = get_optional_field(&mut source, "description");
+        let is_aur = get_optional_field(&mut source, "is_aur").map(|is_aur| is_aur == "true");
+        let providers = get_optional_field(&mut source, "providers")
+            .map(|p| p.split(',').map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+
+        Ok(Self {
+            name,
+            kind,
+            constraint,
+            description,
+            is_aur,
+            providers,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Depends,
+    MakeDepends,
+    CheckDepends,
+    OptDepends,
+}
+
+impl DependencyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Depends => "depends",
+            Self::MakeDepends => "makedepends",
+            Self::CheckDepends => "checkdepends",
+            Self::OptDepends => "optdepends",
+        }
+    }
+}
+
+impl FromStr for DependencyKind {
+    type Err = ModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depends" => Ok(Self::Depends),
+            "makedepends" => Ok(Self::MakeDepends),
+            "checkdepends" => Ok(Self::CheckDepends),
+            "optdepends" => Ok(Self::OptDepends),
+            _ => Err(ModelError::ParseError {
+                field: "kind",
+                source: anyhow!("Unknown dependency kind {}", s),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConstraintOperator {
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = "<=")]
+    LessOrEqual,
+    #[serde(rename = "=")]
+    Equal,
+    #[serde(rename = ">=")]
+    GreaterOrEqual,
+    #[serde(rename = ">")]
+    Greater,
+}
+
+impl ConstraintOperator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Equal => "=",
+            Self::GreaterOrEqual => ">=",
+            Self::Greater => ">",
+        }
+    }
+}
+
+/// Version requirement attached to a package name, e.g. `>=1.2` in `python>=1.2`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionConstraint {
+    pub operator: ConstraintOperator,
+    pub version: String,
+}
+
+impl VersionConstraint {
+    /// Returns true when the version meets the requirement.
+    pub fn is_satisfied_by(&self, version: &PackageVersion) -> bool {
+        let Ok(required) = self.version.parse::<PackageVersion>() else {
+            return false;
+        };
+        let ordering = version.vercmp(&required);
+
+        match self.operator {
+            ConstraintOperator::Less => ordering.is_lt(),
+            ConstraintOperator::LessOrEqual => ordering.is_le(),
+            ConstraintOperator::Equal => ordering.is_eq(),
+            ConstraintOperator::GreaterOrEqual => ordering.is_ge(),
+            ConstraintOperator::Greater => ordering.is_gt(),
+        }
+    }
+
+    /// Splits a pacman style package reference such as `python>=3.9` into the package name and
+    /// its version constraint.
+    pub fn split(reference: &str) -> (&str, Option<Self>) {
+        let reference = reference.trim();
+
+        let Some(idx) = reference.find(['<', '>', '=']) else {
+            return (reference, None);
+        };
+
+        match reference[idx..].parse() {
+            Ok(constraint) => (reference[..idx].trim_end(), Some(constraint)),
+            Err(_) => (reference, None),
+        }
+    }
+}
+
+impl FromStr for VersionConstraint {
+    type Err = ModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let operators = [
+            ("<=", ConstraintOperator::LessOrEqual),
+            (">=", ConstraintOperator::GreaterOrEqual),
+            ("<", ConstraintOperator::Less),
+            (">", ConstraintOperator::Greater),
+            ("=", ConstraintOperator::Equal),
+        ];
+
+        operators
+            .into_iter()
+            .find_map(|(prefix, operator)| {
+                s.strip_prefix(prefix).map(|version| VersionConstraint {
+                    operator,
+                    version: version.trim().to_string(),
+                })
+            })
+            .filter(|constraint| !constraint.version.is_empty())
+            .ok_or_else(|| ModelError::ParseError {
+                field: "constraint",
+                source: anyhow!("Invalid version constraint {}", s),
+            })
+    }
+}
+
+impl Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.operator.as_str(), self.version)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Comment {
+    /// Raw text of the comment header, e.g. "Someone commented on 2023-01-01 10:00 (UTC)"
     pub header: String,
     pub content: String,
+    /// Comment pinned by the package maintainer, usually holding an important notice
+    #[serde(default)]
+    pub is_pinned: bool,
+    /// Id of the comment, the AUR uses it in the `comment-<id>` anchor
+    #[serde(default)]
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default, with = "timestamp::option")]
+    pub posted_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "timestamp::option")]
+    pub edited_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub edited_by: Option<String>,
 }
 
 impl TryFrom<HashMap<String, String>> for Comment {
@@ -217,8 +675,32 @@ impl TryFrom<HashMap<String, String>> for Comment {
 
         let header = getter("header")?;
         let content = getter("content")?;
+        let is_pinned = source.remove("is_pinned").is_some_and(|v| v == "true");
+        let id = get_optional_field(&mut source, "id")
+            .map(|id| id.parse())
+            .transpose()
+            .map_err(|e: ParseIntError| ModelError::ParseError {
+                field: "id",
+                source: anyhow!(e),
+            })?;
 
-        Ok(Self { header, content })
+        let posted_at = get_optional_field(&mut source, "posted_at")
+            .map(|t| parse_timestamp(&t, "posted_at"))
+            .transpose()?;
+        let edited_at = get_optional_field(&mut source, "edited_at")
+            .map(|t| parse_timestamp(&t, "edited_at"))
+            .transpose()?;
+
+        Ok(Self {
+            header,
+            content,
+            is_pinned,
+            id,
+            author: get_optional_field(&mut source, "author"),
+            posted_at,
+            edited_at,
+            edited_by: get_optional_field(&mut source, "edited_by"),
+        })
     }
 }
 
@@ -232,3 +714,472 @@ pub enum ModelError {
         source: anyhow::Error,
     },
 }
+
+/// Package version in the `epoch:pkgver-pkgrel` form, where epoch and pkgrel are optional.
+/// Versions are ordered and equal the same way as by pacman's `vercmp`, see
+/// [`PackageVersion::vercmp`].
+#[derive(Debug, Clone)]
+pub struct PackageVersion {
+    /// Digits before the first colon, `0` when the version has no epoch
+    pub epoch: String,
+    pub pkgver: String,
+    pub pkgrel: Option<String>,
+}
+
+impl FromStr for PackageVersion {
+    type Err = ModelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parse_error = |reason: String| ModelError::ParseError {
+            field: "version",
+            source: anyhow!(reason),
+        };
+
+        // Like pacman, the epoch is recognized only when all characters before ':' are digits
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (epoch, rest) = match s[digits..].strip_prefix(':') {
+            Some(rest) if digits > 0 => (&s[..digits], rest),
+            Some(rest) => ("0", rest),
+            None => ("0", s),
+        };
+
+        let (pkgver, pkgrel) = match rest.rsplit_once('-') {
+            Some((pkgver, pkgrel)) => (pkgver, Some(pkgrel.to_string())),
+            None => (rest, None),
+        };
+
+        if pkgver.is_empty() {
+            return Err(parse_error(format!("Version is missing in {}", s)));
+        }
+
+        Ok(Self {
+            epoch: epoch.to_string(),
+            pkgver: pkgver.to_string(),
+            pkgrel,
+        })
+    }
+}
+
+impl Display for PackageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.epoch != "0" {
+            write!(f, "{}:", self.epoch)?;
+        }
+        write!(f, "{}", self.pkgver)?;
+        if let Some(pkgrel) = &self.pkgrel {
+            write!(f, "-{}", pkgrel)?;
+        }
+        Ok(())
+    }
+}
+
+impl PackageVersion {
+    /// Compares versions the same way as pacman's `vercmp`, so the release is compared only
+    /// when both versions have one.
+    pub fn vercmp(&self, other: &Self) -> Ordering {
+        rpmvercmp(&self.epoch, &other.epoch)
+            .then_with(|| rpmvercmp(&self.pkgver, &other.pkgver))
+            .then_with(|| match (&self.pkgrel, &other.pkgrel) {
+                (Some(a), Some(b)) => rpmvercmp(a, b),
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl Ord for PackageVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.vercmp(other)
+    }
+}
+
+impl PartialOrd for PackageVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for PackageVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.vercmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PackageVersion {}
+
+impl Serialize for PackageVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackageVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Compares version segments the same way as `rpmvercmp` from libalpm. Versions are split into
+/// alphabetic and numeric segments, numeric ones are newer than alphabetic ones and a remaining
+/// alphabetic segment is older than no segment at all, so `1.0alpha` < `1.0` < `1.0.1`.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut one, mut two) = (0, 0);
+
+    while one < a.len() && two < b.len() {
+        let (sep_one, sep_two) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+
+        if one == a.len() || two == b.len() {
+            break;
+        }
+
+        // Longer separator wins, so `1..0` is newer than `1.0`
+        if one - sep_one != two - sep_two {
+            return (one - sep_one).cmp(&(two - sep_two));
+        }
+
+        let is_num = a[one].is_ascii_digit();
+        let in_segment = |c: &u8| {
+            if is_num {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let end_one = one + a[one..].iter().take_while(|c| in_segment(c)).count();
+        let end_two = two + b[two..].iter().take_while(|c| in_segment(c)).count();
+
+        // Segments of different types, numeric one is newer
+        if end_two == two {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let (mut seg_one, mut seg_two) = (&a[one..end_one], &b[two..end_two]);
+
+        if is_num {
+            fn strip_zeros(seg: &[u8]) -> &[u8] {
+                let zeros = seg.iter().take_while(|c| **c == b'0').count();
+                &seg[zeros..]
+            }
+            seg_one = strip_zeros(seg_one);
+            seg_two = strip_zeros(seg_two);
+
+            match seg_one.len().cmp(&seg_two.len()) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+        }
+
+        match seg_one.cmp(seg_two) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+
+        one = end_one;
+        two = end_two;
+    }
+
+    let (rest_one, rest_two) = (&a[one..], &b[two..]);
+
+    if rest_one.is_empty() && rest_two.is_empty() {
+        return Ordering::Equal;
+    }
+
+    // Remaining alphabetic segment never beats an empty string, otherwise the longer one wins
+    if (rest_one.is_empty() && !rest_two[0].is_ascii_alphabetic())
+        || rest_one.first().is_some_and(u8::is_ascii_alphabetic)
+    {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+/// Parses timestamps in any of the forms they are scraped or stored in: the forms displayed by
+/// the AUR (see [`parse_displayed_timestamp`]), RFC 3339 and Unix timestamps in seconds.
+pub fn parse_timestamp(value: &str, field: &'static str) -> Result<DateTime<Utc>, ModelError> {
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<i64>() {
+        return Utc
+            .timestamp_opt(secs, 0)
+            .single()
+            .ok_or_else(|| ModelError::ParseError {
+                field,
+                source: anyhow!("Timestamp {} is out of range", secs),
+            });
+    }
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    parse_displayed_timestamp(value, field)
+}
+
+/// Parses timestamps displayed on the AUR pages: `2023-01-01 10:00 (UTC)` or the date alone,
+/// `2023-01-01`, shown on the packages list and taken as the midnight UTC.
+pub fn parse_displayed_timestamp(
+    value: &str,
+    field: &'static str,
+) -> Result<DateTime<Utc>, ModelError> {
+    let value = value.trim();
+    let parse_error = |reason: String| ModelError::ParseError {
+        field,
+        source: anyhow!(reason),
+    };
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)));
+    }
+
+    // The AUR displays dates in the timezone of the logged in user, anonymous visitors get UTC
+    let (local, zone) = match value.strip_suffix(')').and_then(|v| v.rsplit_once('(')) {
+        Some((local, zone)) => (local.trim(), zone.trim()),
+        None => (value, "UTC"),
+    };
+    if zone != "UTC" {
+        return Err(parse_error(format!("Unsupported timezone {}", zone)));
+    }
+
+    NaiveDateTime::parse_from_str(local, "%Y-%m-%d %H:%M")
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .map_err(|e| parse_error(format!("Cannot parse {}: {}", value, e)))
+}
+
+/// Reads lists stored either as sequences or, in records saved before the lists were split, as
+/// comma-joined strings.
+mod joined_list {
+    use std::{fmt::Display, str::FromStr};
+
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr<T> {
+        List(Vec<T>),
+        Joined(Option<String>),
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + FromStr,
+        T::Err: Display,
+    {
+        match Repr::deserialize(deserializer)? {
+            Repr::List(list) => Ok(list),
+            Repr::Joined(joined) => joined
+                .iter()
+                .flat_map(|joined| joined.split(','))
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse().map_err(D::Error::custom))
+                .collect(),
+        }
+    }
+}
+
+/// Serializes timestamps as RFC 3339 strings in UTC, which sort the same way as the timestamps,
+/// and accepts every form understood by [`parse_timestamp`] to read records saved before
+/// timestamps were typed.
+pub(crate) mod timestamp {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::parse_timestamp;
+
+    pub fn serialize<S: Serializer>(
+        value: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_timestamp(&value, "timestamp").map_err(D::Error::custom)
+    }
+
+    pub mod option {
+        use chrono::{DateTime, Utc};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrapper(#[serde(with = "super")] DateTime<Utc>);
+
+            let value = Option::<Wrapper>::deserialize(deserializer)?;
+            Ok(value.map(|Wrapper(timestamp)| timestamp))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_displayed_timestamp, parse_timestamp, ConstraintOperator, PackageVersion,
+        VersionConstraint,
+    };
+    use chrono::{TimeZone, Utc};
+    use std::cmp::Ordering;
+
+    fn vercmp(a: &str, b: &str) -> Ordering {
+        let a: PackageVersion = a.parse().unwrap();
+        let b: PackageVersion = b.parse().unwrap();
+        a.vercmp(&b)
+    }
+
+    #[test]
+    fn parse_displayed_and_stored_timestamps() {
+        // Arrange
+        let midnight = Utc.with_ymd_and_hms(2023, 10, 31, 0, 0, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2023, 10, 31, 19, 2, 0).unwrap();
+
+        // Act & Assert
+        assert_eq!(
+            parse_displayed_timestamp("2023-10-31", "t").unwrap(),
+            midnight
+        );
+        assert_eq!(
+            parse_displayed_timestamp(" 2023-10-31 19:02 (UTC) ", "t").unwrap(),
+            evening
+        );
+        assert!(parse_displayed_timestamp("2012", "t").is_err());
+        assert!(parse_displayed_timestamp("2023-10-31 19:02 (CET)", "t").is_err());
+
+        assert_eq!(parse_timestamp("1698778920", "t").unwrap(), evening);
+        assert_eq!(
+            parse_timestamp("2023-10-31T19:02:00Z", "t").unwrap(),
+            evening
+        );
+        assert_eq!(parse_timestamp("2023-10-31", "t").unwrap(), midnight);
+    }
+
+    #[test]
+    fn compare_versions_like_vercmp() {
+        // Arrange
+        // Cases taken from the vercmp test suite of pacman
+        let cases = [
+            ("1.5.0", "1.5.0", Ordering::Equal),
+            ("1.5.1", "1.5.0", Ordering::Greater),
+            ("1.5.1", "1.5", Ordering::Greater),
+            ("1.5.0", "1.5", Ordering::Greater),
+            ("1.5b", "1.5", Ordering::Less),
+            ("1.5", "1.5a", Ordering::Greater),
+            ("1.5pre1", "1.5", Ordering::Less),
+            ("1.5pre1", "1.5pre2", Ordering::Less),
+            ("1.5rc1", "1.5pre1", Ordering::Greater),
+            ("1.5.1", "1.5.a", Ordering::Greater),
+            ("1.001", "1.1", Ordering::Equal),
+            ("1.5..1", "1.5.1", Ordering::Greater),
+            ("1.5_1", "1.5.1", Ordering::Equal),
+            ("1.0-1", "1.0-2", Ordering::Less),
+            ("1.0-2", "1.0", Ordering::Equal),
+            ("1.1-1", "1.0-2", Ordering::Greater),
+            ("0:1.0", "1.0", Ordering::Equal),
+            ("1:1.0", "2.0", Ordering::Greater),
+            ("2:1.0-1", "1:3.0-1", Ordering::Greater),
+            ("18446744073709551616:1.0", "1:2.0", Ordering::Greater),
+            ("r1234.abcdef-1", "r1235.abcdef-1", Ordering::Less),
+        ];
+
+        for (a, b, expected) in cases {
+            // Act
+            let ordering = vercmp(a, b);
+            let reversed = vercmp(b, a);
+
+            // Assert
+            assert_eq!(ordering, expected, "{} vs {}", a, b);
+            assert_eq!(reversed, expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn sort_and_equal_versions_like_vercmp() {
+        // Arrange
+        let parse = |v: &str| v.parse::<PackageVersion>().unwrap();
+        let mut versions = [
+            "1:0.1", "1.5rc1", "1.5", "1.5pre1", "1.10-1", "1.5.1", "1.5-2",
+        ]
+        .map(parse)
+        .to_vec();
+
+        // Act
+        versions.sort();
+
+        // Assert
+        assert_eq!(parse("1.001"), parse("1.1"));
+        assert_eq!(parse("0:1.0"), parse("1.0"));
+        assert_ne!(parse("1.0-1"), parse("1.0-2"));
+        let sorted = versions.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            vec!["1.5pre1", "1.5rc1", "1.5", "1.5-2", "1.5.1", "1.10-1", "1:0.1"]
+        );
+        assert_eq!(
+            versions.iter().max().map(|v| v.to_string()),
+            Some("1:0.1".into())
+        );
+    }
+
+    #[test]
+    fn parse_epoch_version_and_release() {
+        // Act
+        let version: PackageVersion = "2:1.2.3-4".parse().unwrap();
+        let no_epoch: PackageVersion = "1.2:3-4-5".parse().unwrap();
+
+        // Assert
+        assert_eq!(version.epoch, "2");
+        assert_eq!(version.pkgver, "1.2.3");
+        assert_eq!(version.pkgrel.as_deref(), Some("4"));
+        assert_eq!(version.to_string(), "2:1.2.3-4");
+        assert_eq!(no_epoch.epoch, "0");
+        assert_eq!(no_epoch.pkgver, "1.2:3-4");
+        assert_eq!(no_epoch.pkgrel.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn check_version_constraints() {
+        // Arrange
+        let constraint = VersionConstraint {
+            operator: ConstraintOperator::GreaterOrEqual,
+            version: "1.10".into(),
+        };
+
+        // Act & Assert
+        assert!(constraint.is_satisfied_by(&"1.10-1".parse().unwrap()));
+        assert!(constraint.is_satisfied_by(&"1.11".parse().unwrap()));
+        assert!(!constraint.is_satisfied_by(&"1.9".parse().unwrap()));
+    }
+}
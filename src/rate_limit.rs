@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use tokio::{
+    sync::{Mutex, Semaphore, SemaphorePermit},
+    time::{sleep, Instant},
+};
+
+/// Token bucket which limits both the number of requests started per second and the number of
+/// requests being in flight at the same time.
+pub struct RateLimiter {
+    requests_per_second: f64,
+    in_flight: Semaphore,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, max_in_flight: usize) -> Self {
+        assert!(
+            requests_per_second > 0.0,
+            "Requests per second must be positive"
+        );
+        assert!(max_in_flight > 0, "Max in-flight requests must be positive");
+
+        Self {
+            requests_per_second,
+            in_flight: Semaphore::new(max_in_flight),
+            bucket: Mutex::new(Bucket {
+                tokens: 1.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent. The request is counted as in flight until the
+    /// returned permit is dropped.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .in_flight
+            .acquire()
+            .await
+            .expect("Rate limiter semaphore is never closed");
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                bucket.refill(self.requests_per_second, self.capacity());
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    None
+                } else {
+                    Some((1.0 - bucket.tokens) / self.requests_per_second)
+                }
+            };
+
+            match wait {
+                Some(secs) => sleep(Duration::from_secs_f64(secs)).await,
+                None => return permit,
+            }
+        }
+    }
+
+    fn capacity(&self) -> f64 {
+        self.requests_per_second.max(1.0)
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, requests_per_second: f64, capacity: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * requests_per_second).min(capacity);
+        self.last_refill = now;
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::Path,
+};
+
+use anyhow::Result;
+use chrono::Utc;
+use tracing::{error, info};
+
+use crate::{
+    database::DatabasePackageIO,
+    incremental::Snapshot,
+    rpc::AurRpcSource,
+    scrap::PACKAGES_PER_PAGE,
+    serialization::{read_binary_file_and_deserialize, save_to_binary_file, serialize_to_bson},
+};
+
+/// Name of the file with tombstones of the removed packages, stored next to the BSON files of
+/// the pages
+pub const REMOVED_FILE_NAME: &str = "removed.bson";
+
+/// Names of the packages listed on the scraped pages, compared with the stored packages to find
+/// the ones deleted from the AUR.
+#[derive(Default)]
+pub struct ListedPackages {
+    names: HashSet<String>,
+    /// Set when the first page of the packages list was scraped
+    has_first_page: bool,
+    /// Number of pages shown on the packages list, when it was read from a scraped page
+    pages_count: Option<u32>,
+    /// Set when a page shorter than the full one or the last of the pages shown on the list was
+    /// scraped, so the end of the list was reached
+    reached_end: bool,
+    /// Set when any page was not scraped, so packages listed on it are unknown
+    pages_missing: bool,
+}
+
+impl ListedPackages {
+    /// Records the number of pages shown on the packages list, so the end of the list is known
+    /// even when the number of packages fills the last page.
+    pub fn record_pages_count(&mut self, pages_count: Option<u32>) {
+        if pages_count.is_some() {
+            self.pages_count = pages_count;
+        }
+    }
+
+    pub fn record_page(&mut self, page: u32, names: Vec<String>) {
+        self.has_first_page |= page == 1;
+        self.reached_end |=
+            names.len() < PACKAGES_PER_PAGE || self.pages_count.is_some_and(|count| page >= count);
+        self.names.extend(names);
+    }
+
+    pub fn record_missing_page(&mut self) {
+        self.pages_missing = true;
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    /// Returns true when the whole packages list was scraped, only then packages missing on it
+    /// may be considered removed.
+    pub fn is_complete(&self) -> bool {
+        self.has_first_page && self.reached_end && !self.pages_missing
+    }
+
+    /// Returns names of the stored packages which are not listed anymore.
+    pub fn unlisted<'a>(&self, stored: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        stored
+            .into_iter()
+            .filter(|name| !self.names.contains(*name))
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Keeps only the packages which are missing in the AUR. Packages list is sorted by popularity,
+/// so packages moving between pages while they are scraped may be skipped by the list.
+async fn confirm_removed(rpc: &AurRpcSource, unlisted: Vec<String>) -> Result<Vec<String>> {
+    if unlisted.is_empty() {
+        return Ok(unlisted);
+    }
+
+    let existing = rpc.find_existing(&unlisted).await?;
+    Ok(unlisted
+        .into_iter()
+        .filter(|name| !existing.contains(name))
+        .collect())
+}
+
+/// Marks packages stored in the databases, which are no longer listed and are missing in the
+/// AUR, as removed.
+pub async fn mark_removed_in_databases(
+    rpc: &AurRpcSource,
+    databases: &[Box<dyn DatabasePackageIO>],
+    listed: &ListedPackages,
+) -> Result<()> {
+    let mut unlisted_by_db = vec![];
+
+    for db in databases {
+        let stored = db.get_all_basic_data().await?;
+        unlisted_by_db.push(listed.unlisted(stored.iter().map(|s| s.basic.name.as_str())));
+    }
+
+    let unlisted = unlisted_by_db
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let removed = confirm_removed(rpc, unlisted.into_iter().collect())
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let removed_at = Utc::now();
+
+    for (db, unlisted) in databases.iter().zip(unlisted_by_db) {
+        for name in unlisted.iter().filter(|name| removed.contains(*name)) {
+            if let Err(e) = db.mark_removed(name, removed_at).await {
+                error!(
+                    "Failed to mark {} as removed in {} database. Caused by: {:#}",
+                    name,
+                    db.name(),
+                    e
+                );
+            }
+        }
+    }
+
+    info!(
+        "Marked {} packages deleted from the AUR as removed",
+        removed.len()
+    );
+
+    Ok(())
+}
+
+/// Saves tombstones of the packages deleted from the AUR since the previous run, along with
+/// the tombstones saved by the previous run, unless their packages are listed again.
+pub async fn save_removed_to_fs(
+    rpc: &AurRpcSource,
+    snapshot: &mut Snapshot,
+    listed: &ListedPackages,
+    previous_dir: &str,
+    dir: &str,
+) -> Result<()> {
+    let unlisted = snapshot
+        .removed()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let removed = confirm_removed(rpc, unlisted).await?;
+    let removed_at = Utc::now();
+
+    let mut tombstones = snapshot.take(&removed);
+    for pkg in &mut tombstones {
+        pkg.removed_at = Some(removed_at);
+    }
+    info!("{} packages were deleted from the AUR", tombstones.len());
+
+    let previous_file = Path::new(previous_dir).join(REMOVED_FILE_NAME);
+    if previous_file.exists() {
+        let previous = read_binary_file_and_deserialize(&previous_file.to_string_lossy())?;
+        tombstones.extend(
+            previous.into_iter().filter(|pkg| {
+                !listed.contains(&pkg.basic.name) && !removed.contains(&pkg.basic.name)
+            }),
+        );
+    }
+
+    let file_name = Path::new(dir).join(REMOVED_FILE_NAME);
+    let serialized = serialize_to_bson(&tombstones)?;
+    save_to_binary_file(&file_name.to_string_lossy(), &serialized).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{save_removed_to_fs, ListedPackages, REMOVED_FILE_NAME};
+    use crate::{
+        database::shared::create_package_data,
+        incremental::Snapshot,
+        models::PackageData,
+        retry::RetryPolicy,
+        rpc::AurRpcSource,
+        scrap::{AurScraper, ScraperConfig, PACKAGES_PER_PAGE},
+        serialization::{read_binary_file_and_deserialize, save_to_binary_file, serialize_to_bson},
+        stub_server::{StubResponse, StubServer},
+    };
+    use anyhow::Result;
+    use chrono::Utc;
+
+    fn names(prefix: &str, count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("{}{}", prefix, i)).collect()
+    }
+
+    #[test]
+    fn complete_only_when_all_pages_were_scraped() {
+        // Arrange
+        let mut from_first = ListedPackages::default();
+        let mut from_second = ListedPackages::default();
+        let mut with_missing = ListedPackages::default();
+        let mut without_end = ListedPackages::default();
+        let mut with_full_last = ListedPackages::default();
+
+        // Act
+        from_first.record_page(1, names("a", PACKAGES_PER_PAGE));
+        from_first.record_page(2, names("b", 10));
+        from_second.record_page(2, names("b", 10));
+        with_missing.record_page(1, names("a", PACKAGES_PER_PAGE));
+        with_missing.record_missing_page();
+        with_missing.record_page(3, names("c", 10));
+        without_end.record_page(1, names("a", PACKAGES_PER_PAGE));
+        with_full_last.record_pages_count(Some(2));
+        with_full_last.record_page(1, names("a", PACKAGES_PER_PAGE));
+        with_full_last.record_page(2, names("b", PACKAGES_PER_PAGE));
+
+        // Assert
+        assert!(from_first.is_complete());
+        assert!(!from_second.is_complete());
+        assert!(!with_missing.is_complete());
+        assert!(!without_end.is_complete());
+        assert!(with_full_last.is_complete());
+    }
+
+    #[test]
+    fn find_stored_packages_which_are_not_listed() {
+        // Arrange
+        let mut listed = ListedPackages::default();
+        listed.record_page(1, vec!["yay".into(), "paru".into()]);
+
+        // Act
+        let unlisted = listed.unlisted(["yay", "pikaur", "paru", "aurman"]);
+
+        // Assert
+        assert_eq!(unlisted, vec!["pikaur", "aurman"]);
+    }
+
+    fn package(name: &str) -> PackageData {
+        let mut pkg = create_package_data();
+        pkg.basic.name = name.into();
+        pkg
+    }
+
+    #[tokio::test]
+    async fn save_tombstones_of_packages_missing_in_aur() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::ZERO, |_| {
+            StubResponse::ok(
+                r#"{"resultcount":1,"results":[{"Name":"moved","PackageBase":"moved",
+                "Version":"1.0-1","NumVotes":1,"Popularity":0.1,"OutOfDate":null,
+                "Maintainer":null,"FirstSubmitted":1500000000,"LastModified":1600000000}],
+                "type":"multiinfo","version":5}"#,
+            )
+        })
+        .await;
+        let scraper = Arc::new(AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: RetryPolicy {
+                max_attempts: 1,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(10),
+            },
+            max_comment_pages: None,
+        }));
+        let rpc = AurRpcSource::new(scraper, server.url(""));
+
+        let dir = std::env::temp_dir().join(format!("aur-raider-removed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let dir_path = dir.to_str().unwrap();
+        let mut previous_tombstones = vec![package("old"), package("back")];
+        for pkg in &mut previous_tombstones {
+            pkg.removed_at = Some(Utc::now());
+        }
+        let serialized = serialize_to_bson(&previous_tombstones)?;
+        save_to_binary_file(&dir.join(REMOVED_FILE_NAME).to_string_lossy(), &serialized).await?;
+
+        let mut snapshot = Snapshot::new(vec![package("kept"), package("moved"), package("gone")]);
+        let mut listed = ListedPackages::default();
+        listed.record_page(1, vec!["kept".into(), "back".into()]);
+        snapshot.diff(vec![package("kept").basic, package("back").basic]);
+
+        // Act
+        save_removed_to_fs(&rpc, &mut snapshot, &listed, dir_path, dir_path).await?;
+        let tombstones =
+            read_binary_file_and_deserialize(&dir.join(REMOVED_FILE_NAME).to_string_lossy())?;
+        std::fs::remove_dir_all(&dir)?;
+
+        // Assert
+        let names = tombstones
+            .iter()
+            .map(|pkg| pkg.basic.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["gone", "old"]);
+        assert!(tombstones.iter().all(|pkg| pkg.removed_at.is_some()));
+        assert!(tombstones[0].additional.is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use rand::Rng;
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+
+/// Describes how many times and how long to wait before a failed request is repeated.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts including the first one
+    pub max_attempts: u32,
+    /// Delay before the second attempt, doubled for every following attempt
+    pub base_delay: Duration,
+    /// Upper bound of the delay computed by the exponential backoff and of the delay requested
+    /// by the server
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the attempt following the given one. The exponential backoff is
+    /// randomized with an equal jitter, so at least half of the computed delay is always awaited.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = exp / 2;
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+
+    /// Returns the delay before the attempt following the given one, preferring the delay
+    /// requested by the server as long as it does not exceed the maximum delay.
+    pub fn delay(&self, attempt: u32, requested: Option<Duration>) -> Duration {
+        match requested {
+            Some(requested) => requested.min(self.max_delay),
+            None => self.backoff(attempt),
+        }
+    }
+}
+
+/// Error returned after all attempts allowed by the retry policy have failed.
+#[derive(Debug)]
+pub struct RetriesExhausted {
+    pub url: String,
+    pub attempts: u32,
+}
+
+impl fmt::Display for RetriesExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to fetch {} after {} attempts",
+            self.url, self.attempts
+        )
+    }
+}
+
+tokio::task_local! {
+    /// Highest number of attempts needed to fetch a page of the package scraped by the task
+    static PACKAGE_ATTEMPTS: Cell<u32>;
+}
+
+/// Runs the scraping of a single package, returning its result together with the highest
+/// number of attempts needed to fetch any of its pages.
+pub async fn track_attempts<F: Future>(scraping: F) -> (F::Output, u32) {
+    PACKAGE_ATTEMPTS
+        .scope(Cell::new(0), async {
+            let output = scraping.await;
+            (output, PACKAGE_ATTEMPTS.with(Cell::get))
+        })
+        .await
+}
+
+/// Records the number of attempts needed to fetch a page of the package being scraped. Pages
+/// fetched outside of [`track_attempts`] are not recorded.
+pub fn record_page_attempts(attempts: u32) {
+    let _ = PACKAGE_ATTEMPTS.try_with(|max| max.set(max.get().max(attempts)));
+}
+
+/// Returns true for response statuses after which the request is worth repeating.
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Returns true for errors caused by the network rather than by the request itself.
+pub fn is_transient_error(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+}
+
+/// Reads the delay requested by the server in the `Retry-After` header of 429 and 503 responses.
+/// Both delay-seconds and HTTP-date forms are supported.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    if !matches!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    httpdate::parse_http_date(value)
+        .ok()?
+        .duration_since(SystemTime::now())
+        .ok()
+}
+
+/// Histogram of the number of attempts needed to scrape packages. A package takes as many
+/// attempts as the page of the package that needed the most of them.
+#[derive(Default)]
+pub struct AttemptsMetrics {
+    succeeded: Mutex<BTreeMap<u32, usize>>,
+    failed: Mutex<usize>,
+}
+
+impl AttemptsMetrics {
+    pub fn record_success(&self, attempts: u32) {
+        *self.succeeded.lock().unwrap().entry(attempts).or_default() += 1;
+    }
+
+    pub fn record_failure(&self) {
+        *self.failed.lock().unwrap() += 1;
+    }
+
+    pub fn summary(&self) -> String {
+        let succeeded = self.succeeded.lock().unwrap();
+        let histogram = succeeded
+            .iter()
+            .map(|(attempts, packages)| format!("{} attempt(s): {}", attempts, packages))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "scraped packages by attempts [{}], failed after all attempts: {}",
+            histogram,
+            self.failed.lock().unwrap()
+        )
+    }
+}
@@ -0,0 +1,578 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{TimeZone, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+use tokio::task::JoinSet;
+use tracing::{error, instrument};
+use url::Url;
+
+use crate::{
+    models::{
+        AdditionalPackageData, BasicPackageData, DependencyKind, PackageData, PackageDependency,
+        PackageRelation, VersionConstraint,
+    },
+    scrap::AurScraper,
+};
+
+pub static AUR_URL: &str = "https://aur.archlinux.org";
+
+/// Maximum number of packages requested by a single `info` call, keeps URLs below the length
+/// accepted by the AUR
+const INFO_BATCH_SIZE: usize = 100;
+
+/// Field matched by the `search` call of the RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SearchBy {
+    Name,
+    NameDesc,
+    Maintainer,
+    Depends,
+    MakeDepends,
+    OptDepends,
+    CheckDepends,
+    Submitter,
+    Provides,
+    Conflicts,
+    Replaces,
+    Keywords,
+    Groups,
+    CoMaintainers,
+}
+
+impl SearchBy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::NameDesc => "name-desc",
+            Self::Maintainer => "maintainer",
+            Self::Depends => "depends",
+            Self::MakeDepends => "makedepends",
+            Self::OptDepends => "optdepends",
+            Self::CheckDepends => "checkdepends",
+            Self::Submitter => "submitter",
+            Self::Provides => "provides",
+            Self::Conflicts => "conflicts",
+            Self::Replaces => "replaces",
+            Self::Keywords => "keywords",
+            Self::Groups => "groups",
+            Self::CoMaintainers => "comaintainers",
+        }
+    }
+}
+
+/// Fetches packages from the JSON interface of the AUR (`/rpc/v5`). The RPC lacks comments,
+/// so they are still scraped from the package pages.
+pub struct AurRpcSource {
+    scraper: Arc<AurScraper>,
+    base_url: String,
+    fetch_comments: bool,
+}
+
+impl AurRpcSource {
+    pub fn new(scraper: Arc<AurScraper>, base_url: impl Into<String>) -> Self {
+        Self {
+            scraper,
+            base_url: base_url.into(),
+            fetch_comments: true,
+        }
+    }
+
+    /// Skips scraping of comments, so every package is fetched without touching HTML pages.
+    pub fn without_comments(mut self) -> Self {
+        self.fetch_comments = false;
+        self
+    }
+
+    /// Fetches full data of the packages, names missing in the AUR are skipped.
+    #[instrument(skip(self, names), fields(packages = names.len()))]
+    pub async fn info(&self, names: &[String]) -> Result<Vec<PackageData>> {
+        let mut packages = into_packages(self.info_results(names).await?, &self.base_url);
+
+        link_split_packages(&mut packages);
+
+        if self.fetch_comments {
+            self.fetch_comments(&mut packages).await;
+        }
+
+        Ok(packages)
+    }
+
+    /// Returns names of the packages which still exist in the AUR.
+    #[instrument(skip(self, names), fields(packages = names.len()))]
+    pub async fn find_existing(&self, names: &[String]) -> Result<HashSet<String>> {
+        Ok(self
+            .info_results(names)
+            .await?
+            .into_iter()
+            .map(|result| result.name)
+            .collect())
+    }
+
+    /// Searches packages by the given field and fetches full data of the found ones.
+    #[instrument(skip(self))]
+    pub async fn search(&self, arg: &str, by: SearchBy) -> Result<Vec<PackageData>> {
+        let mut url = Url::parse(&format!("{}/rpc/v5/search", self.base_url))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid RPC URL {}", self.base_url))?
+            .push(arg);
+        url.query_pairs_mut().append_pair("by", by.as_str());
+
+        let names = self
+            .call(url.as_str())
+            .await?
+            .into_iter()
+            .map(|result| result.name)
+            .collect::<Vec<_>>();
+
+        self.info(&names).await
+    }
+
+    /// Fetches packages listed on the packages list, names missing in the AUR are returned as
+    /// partial records.
+    pub async fn get_packages(
+        &self,
+        packages_basic_data: Vec<BasicPackageData>,
+    ) -> Result<Vec<PackageData>> {
+        let names = packages_basic_data
+            .iter()
+            .map(|basic| basic.name.clone())
+            .collect::<Vec<_>>();
+
+        let mut fetched = self
+            .info(&names)
+            .await?
+            .into_iter()
+            .map(|pkg| (pkg.basic.name.clone(), pkg))
+            .collect::<HashMap<_, _>>();
+
+        Ok(packages_basic_data
+            .into_iter()
+            .map(|basic| match fetched.remove(&basic.name) {
+                Some(pkg) => pkg,
+                None => {
+                    error!(
+                        "Saving {} as partial record. Package is missing in the RPC results",
+                        basic.name
+                    );
+                    PackageData::partial(basic)
+                }
+            })
+            .collect())
+    }
+
+    async fn info_results(&self, names: &[String]) -> Result<Vec<RpcPackage>> {
+        let mut results = vec![];
+
+        for batch in names.chunks(INFO_BATCH_SIZE) {
+            let mut url = Url::parse(&format!("{}/rpc/v5/info", self.base_url))?;
+            for name in batch {
+                url.query_pairs_mut().append_pair("arg[]", name);
+            }
+
+            results.extend(self.call(url.as_str()).await?);
+        }
+
+        Ok(results)
+    }
+
+    async fn call(&self, url: &str) -> Result<Vec<RpcPackage>> {
+        let body = self.scraper.get_body(url).await?;
+        let response: RpcResponse = serde_json::from_str(&body)
+            .with_context(|| format!("Cannot parse RPC response from {}", url))?;
+
+        if response.kind == "error" {
+            bail!(
+                "RPC responded with error: {}",
+                response.error.unwrap_or_default()
+            );
+        }
+
+        Ok(response.results)
+    }
+
+    /// Scrapes comments of the packages from their pages, requests are throttled by the
+    /// scraper.
+    pub async fn fetch_comments(&self, packages: &mut [PackageData]) {
+        let mut set = JoinSet::new();
+
+        for (idx, pkg) in packages.iter().enumerate() {
+            let url = format!("{}/packages/{}", self.base_url, pkg.basic.name);
+            let scraper = self.scraper.clone();
+
+            set.spawn(async move {
+                let comments = scraper
+                    .track_package(scraper.get_package_comments(&url))
+                    .await;
+                (idx, comments)
+            });
+        }
+
+        while let Some(task_result) = set.join_next().await {
+            match task_result {
+                Ok((idx, Ok(comments))) => packages[idx].comments = comments,
+                Ok((idx, Err(e))) => error!(
+                    "Saving {} without comments. Caused by: {:#}",
+                    packages[idx].basic.name, e
+                ),
+                Err(e) => error!("{}", e),
+            }
+        }
+    }
+}
+
+/// Converts the RPC results to packages, records which cannot be converted are skipped, so a
+/// single malformed record does not fail the whole batch.
+pub fn into_packages(results: Vec<RpcPackage>, base_url: &str) -> Vec<PackageData> {
+    results
+        .into_iter()
+        .filter_map(|result| {
+            let name = result.name.clone();
+            match result.into_package_data(base_url) {
+                Ok(pkg) => Some(pkg),
+                Err(e) => {
+                    error!("Skipping {}. Caused by: {:#}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Links split packages built from the same package base. Only packages fetched together are
+/// linked, as the RPC does not list members of the package base.
+pub fn link_split_packages(packages: &mut [PackageData]) {
+    let mut bases: HashMap<String, Vec<String>> = HashMap::new();
+
+    for pkg in packages.iter() {
+        if let Some(base) = pkg.additional.as_ref().and_then(|a| a.package_base.clone()) {
+            bases.entry(base).or_default().push(pkg.basic.name.clone());
+        }
+    }
+
+    for pkg in packages.iter_mut() {
+        let name = &pkg.basic.name;
+        if let Some(additional) = &mut pkg.additional {
+            if let Some(members) = additional.package_base.as_ref().and_then(|b| bases.get(b)) {
+                additional.split_packages =
+                    members.iter().filter(|m| *m != name).cloned().collect();
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(rename = "type")]
+    kind: String,
+    error: Option<String>,
+    #[serde(default)]
+    results: Vec<RpcPackage>,
+}
+
+/// Package returned by the RPC and stored in the metadata archive. Results of `search` lack the
+/// fields marked as default, which are also omitted when empty.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RpcPackage {
+    pub name: String,
+    package_base: String,
+    version: String,
+    description: Option<String>,
+    #[serde(rename = "URL")]
+    url: Option<String>,
+    num_votes: i32,
+    popularity: f32,
+    out_of_date: Option<i64>,
+    maintainer: Option<String>,
+    #[serde(default)]
+    submitter: Option<String>,
+    first_submitted: i64,
+    last_modified: i64,
+    #[serde(default)]
+    depends: Vec<String>,
+    #[serde(default)]
+    make_depends: Vec<String>,
+    #[serde(default)]
+    check_depends: Vec<String>,
+    #[serde(default)]
+    opt_depends: Vec<String>,
+    #[serde(default)]
+    conflicts: Vec<String>,
+    #[serde(default)]
+    provides: Vec<String>,
+    #[serde(default)]
+    replaces: Vec<String>,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    license: Vec<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    co_maintainers: Vec<String>,
+}
+
+impl RpcPackage {
+    pub fn into_package_data(self, base_url: &str) -> Result<PackageData> {
+        let timestamp = |secs: i64, field: &str| {
+            Utc.timestamp_opt(secs, 0)
+                .single()
+                .ok_or_else(|| anyhow!("Invalid {} timestamp of {}", field, self.name))
+        };
+
+        let basic = BasicPackageData {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            path_to_additional_data: format!("/{}", self.name),
+            votes: self.num_votes,
+            popularity: self.popularity,
+            description: self.description.clone().unwrap_or_default(),
+            maintainer: self.maintainer.clone().unwrap_or_default(),
+            last_updated: timestamp(self.last_modified, "last modified")?,
+        };
+
+        let out_of_date = self
+            .out_of_date
+            .map(|secs| timestamp(secs, "out-of-date"))
+            .transpose()?;
+
+        let additional = AdditionalPackageData {
+            git_clone_url: format!("{}/{}.git", base_url, self.package_base),
+            keywords: self.keywords,
+            licenses: self.license,
+            conflicts: parse_relations(&self.conflicts)?,
+            provides: parse_relations(&self.provides)?,
+            submitter: self.submitter.unwrap_or_default(),
+            first_submitted: timestamp(self.first_submitted, "first submitted")?,
+            package_base: Some(self.package_base),
+            split_packages: vec![],
+            // Sources and the last packager are shown only on the package page
+            sources: vec![],
+            upstream_url: self.url,
+            groups: self.groups,
+            replaces: self.replaces,
+            co_maintainers: self.co_maintainers,
+            last_packager: None,
+            out_of_date,
+            votes: Some(self.num_votes),
+            popularity: Some(self.popularity),
+        };
+
+        let dependencies = [
+            (DependencyKind::Depends, self.depends),
+            (DependencyKind::MakeDepends, self.make_depends),
+            (DependencyKind::CheckDepends, self.check_depends),
+            (DependencyKind::OptDepends, self.opt_depends),
+        ]
+        .into_iter()
+        .flat_map(|(kind, deps)| deps.into_iter().map(move |dep| to_dependency(kind, &dep)))
+        .collect();
+
+        Ok(PackageData {
+            basic,
+            additional: Some(additional),
+            dependencies,
+            required_by: vec![],
+            comments: vec![],
+            removed_at: None,
+        })
+    }
+}
+
+fn parse_relations(relations: &[String]) -> Result<Vec<PackageRelation>> {
+    relations
+        .iter()
+        .map(|relation| Ok(relation.parse::<PackageRelation>()?))
+        .collect()
+}
+
+/// Converts the PKGBUILD dependency entry, optional dependencies are followed by the reason
+/// after a colon and a space, e.g. `gtk3: for the GUI`. A colon alone may belong to the epoch of
+/// the version constraint, e.g. `foo>=1:2.0`.
+fn to_dependency(kind: DependencyKind, entry: &str) -> PackageDependency {
+    let (reference, description) = match entry.split_once(": ") {
+        Some((reference, description)) if kind == DependencyKind::OptDepends => {
+            (reference, Some(description.trim().to_string()))
+        }
+        _ => (entry, None),
+    };
+    let (name, constraint) = VersionConstraint::split(reference);
+
+    PackageDependency {
+        name: name.to_string(),
+        kind,
+        constraint,
+        description: description.filter(|d| !d.is_empty()),
+        // The RPC does not tell whether the dependency comes from the AUR or official repos
+        is_aur: None,
+        providers: vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{to_dependency, AurRpcSource, SearchBy};
+    use crate::{
+        models::{ConstraintOperator, DependencyKind},
+        retry::RetryPolicy,
+        scrap::{AurScraper, ScraperConfig},
+        stub_server::{StubResponse, StubServer},
+    };
+    use anyhow::Result;
+    use chrono::{TimeZone, Utc};
+
+    fn scraper() -> Arc<AurScraper> {
+        Arc::new(AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 4,
+            retry: RetryPolicy {
+                max_attempts: 1,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(10),
+            },
+            max_comment_pages: None,
+        }))
+    }
+
+    async fn rpc_server() -> StubServer {
+        StubServer::start(Duration::ZERO, |path| {
+            if path.starts_with("/rpc/v5/info") {
+                StubResponse::ok(include_str!("../tests/fixtures/rpc_info.json"))
+            } else if path.starts_with("/rpc/v5/search/yay?by=name") {
+                StubResponse::ok(include_str!("../tests/fixtures/rpc_search.json"))
+            } else if path.starts_with("/packages/") {
+                StubResponse::ok(
+                    r#"<div class="comments package-comments">
+                    <h4 id="comment-1" class="comment-header">tester commented</h4>
+                    <div class="article-content"><p>Works</p></div>
+                    </div>"#,
+                )
+            } else {
+                StubResponse {
+                    status: 404,
+                    headers: vec![],
+                    body: String::new(),
+                }
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn map_info_results_to_package_data() -> Result<()> {
+        // Arrange
+        let server = rpc_server().await;
+        let rpc = AurRpcSource::new(scraper(), server.url(""));
+
+        // Act
+        let packages = rpc.info(&["yay".into(), "yay-bin".into()]).await?;
+
+        // Assert
+        assert_eq!(packages.len(), 2);
+
+        let yay = &packages[0];
+        assert_eq!(yay.basic.name, "yay");
+        assert_eq!(yay.basic.version, "12.1.3-1");
+        assert_eq!(yay.basic.maintainer, "jguer");
+        assert_eq!(
+            yay.basic.last_updated,
+            Utc.timestamp_opt(1692013412, 0).unwrap()
+        );
+
+        let additional = yay.additional.as_ref().unwrap();
+        assert_eq!(additional.git_clone_url, server.url("/yay.git"));
+        assert_eq!(additional.licenses, vec!["GPL-3.0-or-later"]);
+        assert_eq!(additional.co_maintainers, vec!["gitlabbot"]);
+        assert_eq!(additional.keywords, vec!["arm", "AUR", "go", "helper"]);
+
+        let go = &yay.dependencies[2];
+        assert_eq!(go.name, "go");
+        assert_eq!(go.kind, DependencyKind::MakeDepends);
+        let git = &yay.dependencies[0];
+        let constraint = git.constraint.as_ref().unwrap();
+        assert_eq!(constraint.operator, ConstraintOperator::GreaterOrEqual);
+        assert_eq!(constraint.version, "6.0");
+        let sudo = yay.dependencies.last().unwrap();
+        assert_eq!(sudo.kind, DependencyKind::OptDepends);
+        assert_eq!(sudo.description.as_deref(), Some("privilege elevation"));
+
+        assert!(yay.dependencies.iter().all(|dep| dep.is_aur.is_none()));
+
+        let yay_bin = &packages[1];
+        assert_eq!(yay_bin.basic.maintainer, "");
+        assert_eq!(
+            yay_bin.additional.as_ref().unwrap().out_of_date,
+            Utc.timestamp_opt(1692526352, 0).single()
+        );
+        assert_eq!(yay_bin.additional.as_ref().unwrap().provides[0].name, "yay");
+
+        assert_eq!(yay.comments.len(), 1);
+        assert_eq!(yay.comments[0].content, "Works");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search_and_fetch_found_packages_in_batches() -> Result<()> {
+        // Arrange
+        let server = rpc_server().await;
+        let rpc = AurRpcSource::new(scraper(), server.url("")).without_comments();
+
+        // Act
+        let packages = rpc.search("yay", SearchBy::Name).await?;
+
+        // Assert
+        assert_eq!(packages.len(), 2);
+        assert!(packages.iter().all(|pkg| pkg.comments.is_empty()));
+        // One search call and a single info batch
+        assert_eq!(server.served(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_malformed_records_of_the_batch() -> Result<()> {
+        // Arrange
+        // The first submission of yay-bin lies beyond the range of valid timestamps
+        let server = StubServer::start(Duration::ZERO, |_| {
+            StubResponse::ok(include_str!("../tests/fixtures/rpc_info.json").replace(
+                "\"FirstSubmitted\":1489167429",
+                &format!("\"FirstSubmitted\":{}", i64::MAX),
+            ))
+        })
+        .await;
+        let rpc = AurRpcSource::new(scraper(), server.url("")).without_comments();
+
+        // Act
+        let packages = rpc.info(&["yay".into(), "yay-bin".into()]).await?;
+
+        // Assert
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].basic.name, "yay");
+        assert_eq!(server.served(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_optional_dependency_reason_keeping_epoch() {
+        // Act
+        let with_epoch = to_dependency(DependencyKind::OptDepends, "foo>=1:2.0: for tests");
+        let without_reason = to_dependency(DependencyKind::OptDepends, "bar>=1:2.0");
+
+        // Assert
+        assert_eq!(with_epoch.name, "foo");
+        assert_eq!(with_epoch.constraint.unwrap().version, "1:2.0");
+        assert_eq!(with_epoch.description.as_deref(), Some("for tests"));
+        assert_eq!(without_reason.name, "bar");
+        assert_eq!(without_reason.constraint.unwrap().version, "1:2.0");
+        assert!(without_reason.description.is_none());
+    }
+}
@@ -1,151 +1,496 @@
 use std::{
     collections::HashMap,
-    sync::Arc,
+    future::Future,
+    sync::{Arc, Mutex},
     thread,
-    time::{Duration, Instant},
+    time::Duration,
 };
 
 use crate::{
-    html::{delete_tags, extract_attribute_value},
-    models::{AdditionalPackageData, BasicPackageData, Comment, PackageData, PackageDependency},
+    html::{element_text, extract_attribute_value, to_markdown},
+    models::{
+        parse_displayed_timestamp, AdditionalPackageData, BasicPackageData, Comment,
+        DependencyKind, PackageData, PackageDependency, Source,
+    },
+    rate_limit::RateLimiter,
+    retry::{
+        is_transient_error, is_transient_status, record_page_attempts, retry_after, track_attempts,
+        AttemptsMetrics, RetriesExhausted, RetryPolicy,
+    },
     selectors::*,
 };
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use reqwest::Client;
 use scraper::{ElementRef, Html};
+use tokio::sync::OnceCell;
 use tokio::task::JoinSet;
-use tracing::{debug, error, info, instrument};
+use tokio::time::sleep;
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
 
 pub static AUR_BASE_URL: &str = "https://aur.archlinux.org/packages";
-pub static AUR_PAGE_QUERY: &str = "?PP=250&SeB=nd&SB=p&O=";
+pub static AUR_PKGBASE_URL: &str = "https://aur.archlinux.org/pkgbase";
+/// Number of packages shown on a full page of the packages list
+pub const PACKAGES_PER_PAGE: usize = 250;
+
+/// Number of comments displayed on a single comment page of the package
+const COMMENTS_PER_PAGE: usize = 10;
+
+/// Search and sort parameters of the packages list, passed as they are to the AUR website.
+pub struct PackagesListQuery {
+    /// Field searched for the keywords (`SeB` parameter)
+    pub search_by: &'static str,
+    /// Keywords to search for (`K` parameter), all packages are listed when empty
+    pub keywords: String,
+    /// Field by which packages are sorted (`SB` parameter)
+    pub sort_by: &'static str,
+    /// `a` for the ascending and `d` for the descending order (`SO` parameter)
+    pub sort_order: &'static str,
+}
+
+impl Default for PackagesListQuery {
+    /// All packages, the most popular first
+    fn default() -> Self {
+        Self {
+            search_by: "nd",
+            keywords: String::new(),
+            sort_by: "p",
+            sort_order: "d",
+        }
+    }
+}
+
+impl PackagesListQuery {
+    /// Query listing only the package with the given name.
+    pub fn exact_name(name: &str) -> Self {
+        Self {
+            search_by: "N",
+            keywords: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the URL of the page of the packages list, pages are numbered from 1.
+    pub fn page_url(&self, page: u32) -> String {
+        let offset = (page as usize - 1) * PACKAGES_PER_PAGE;
+        let mut url = Url::parse(AUR_BASE_URL).unwrap();
+        url.query_pairs_mut()
+            .append_pair("PP", &PACKAGES_PER_PAGE.to_string())
+            .append_pair("SeB", self.search_by)
+            .append_pair("K", &self.keywords)
+            .append_pair("SB", self.sort_by)
+            .append_pair("SO", self.sort_order)
+            .append_pair("O", &offset.to_string());
+        url.into()
+    }
+
+    /// Returns `true` when only some of the AUR packages may be listed: keywords are given or
+    /// packages are searched by other field than the name and description, e.g. searching by
+    /// the maintainer without keywords lists orphaned packages.
+    pub fn is_filtered(&self) -> bool {
+        !self.keywords.is_empty() || self.search_by != Self::default().search_by
+    }
+}
+
+/// Page of the packages list.
+pub struct PackagesListPage {
+    pub packages: Vec<BasicPackageData>,
+    /// Number of packages found, shown above the packages list
+    pub packages_count: Option<usize>,
+    /// Number of pages of the packages list, shown above the packages list
+    pub pages_count: Option<u32>,
+}
+
+impl PackagesListPage {
+    /// Returns `true` when the page is not full, so no pages follow it.
+    pub fn is_last(&self) -> bool {
+        self.packages.len() < PACKAGES_PER_PAGE
+    }
+
+    /// Returns the last page to scrape, given the number of this page. The number of pages
+    /// shown on the page is used when the last page is not known yet, scraping ends at this page
+    /// when it is not full.
+    pub fn last_page(&self, page: u32, end_page: Option<u32>) -> Option<u32> {
+        if self.is_last() {
+            Some(page)
+        } else {
+            end_page.or(self.pages_count)
+        }
+    }
+}
+
+/// Data scraped from the package details page and its comment pages.
+struct PackageDetails {
+    additional: AdditionalPackageData,
+    dependencies: Vec<PackageDependency>,
+    required_by: Vec<String>,
+    comments: Vec<Comment>,
+}
+
+pub struct ScraperConfig {
+    /// Maximum number of requests sent to the AUR per second
+    pub requests_per_second: f64,
+    /// Maximum number of requests awaiting the AUR response at the same time
+    pub max_in_flight: usize,
+    /// Policy applied to requests which failed because of a timeout, 429 or 5xx response
+    pub retry: RetryPolicy,
+    /// Maximum number of comment pages scraped for each package, all pages when `None`
+    pub max_comment_pages: Option<usize>,
+}
 
 pub struct AurScraper {
     http_client: Client,
+    rate_limiter: RateLimiter,
+    retry_policy: RetryPolicy,
+    max_comment_pages: Option<usize>,
+    attempts: AttemptsMetrics,
+    /// Members of the package bases, fetched once and shared by all split packages of a base
+    package_bases: Mutex<HashMap<String, Arc<OnceCell<Vec<String>>>>>,
+    /// URL to which paths of the package pages are appended
+    packages_url: String,
 }
 
 impl AurScraper {
-    pub fn new() -> Self {
+    pub fn new(config: ScraperConfig) -> Self {
         Self {
             http_client: Client::new(),
+            rate_limiter: RateLimiter::new(config.requests_per_second, config.max_in_flight),
+            retry_policy: config.retry,
+            max_comment_pages: config.max_comment_pages,
+            attempts: AttemptsMetrics::default(),
+            package_bases: Mutex::new(HashMap::new()),
+            packages_url: AUR_BASE_URL.to_string(),
         }
     }
 
-    async fn get_parsed_page(&self, url: &str) -> Result<Html> {
-        Self::get_parsed_page_with_client(self.http_client.clone(), url).await
+    /// Fetches the package pages from the given server instead of the AUR.
+    #[cfg(test)]
+    fn with_packages_url(mut self, packages_url: &str) -> Self {
+        self.packages_url = packages_url.to_string();
+        self
     }
 
-    #[instrument(skip(http_client))]
-    async fn get_parsed_page_with_client(http_client: Client, url: &str) -> Result<Html> {
-        let response = http_client
-            .get(url)
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await?;
-        let body = response.text().await?;
+    /// Returns the summary of attempts needed to scrape packages since the scraper was created.
+    pub fn attempts_summary(&self) -> String {
+        self.attempts.summary()
+    }
+
+    /// Runs the scraping of a single package and records the number of attempts it took. Only
+    /// packages which failed because all attempts of a page were used up are counted as failed.
+    pub async fn track_package<T>(&self, scraping: impl Future<Output = Result<T>>) -> Result<T> {
+        let (result, attempts) = track_attempts(scraping).await;
+
+        match &result {
+            Ok(_) => self.attempts.record_success(attempts),
+            Err(e) if e.downcast_ref::<RetriesExhausted>().is_some() => {
+                self.attempts.record_failure()
+            }
+            Err(_) => {}
+        }
+
+        result
+    }
+
+    async fn get_parsed_page(&self, url: &str) -> Result<Html> {
+        let body = self.get_body(url).await?;
         Ok(Html::parse_document(&body))
     }
 
-    #[instrument(skip(self))]
-    pub async fn get_packages_basic_data_from_page(
-        &self,
+    /// Fetches the body of the response, honouring the rate limit and the retry policy.
+    pub async fn get_body(&self, url: &str) -> Result<String> {
+        let (body, attempts) = Self::get_body_with_client(
+            self.http_client.clone(),
+            &self.rate_limiter,
+            &self.retry_policy,
+            url,
+        )
+        .await
+        .inspect_err(|e| {
+            if let Some(exhausted) = e.downcast_ref::<RetriesExhausted>() {
+                record_page_attempts(exhausted.attempts);
+            }
+        })?;
+
+        record_page_attempts(attempts);
+        Ok(body)
+    }
+
+    #[instrument(skip(http_client, rate_limiter, retry_policy))]
+    async fn get_body_with_client(
+        http_client: Client,
+        rate_limiter: &RateLimiter,
+        retry_policy: &RetryPolicy,
         url: &str,
-    ) -> Result<Vec<BasicPackageData>> {
+    ) -> Result<(String, u32)> {
+        let mut attempt = 1;
+
+        loop {
+            let (err, requested_delay) = {
+                let _permit = rate_limiter.acquire().await;
+                let response = http_client
+                    .get(url)
+                    .timeout(Duration::from_secs(10))
+                    .send()
+                    .await;
+
+                match response {
+                    Ok(response) if is_transient_status(response.status()) => {
+                        let delay = retry_after(&response);
+                        (
+                            anyhow!("Server responded with {}", response.status()),
+                            delay,
+                        )
+                    }
+                    Ok(response) => {
+                        let response = response.error_for_status()?;
+                        match response.text().await {
+                            Ok(body) => {
+                                if attempt > 1 {
+                                    info!("Fetched {} after {} attempts", url, attempt);
+                                }
+                                return Ok((body, attempt));
+                            }
+                            Err(e) if is_transient_error(&e) => (anyhow!(e), None),
+                            Err(e) => return Err(e.into()),
+                        }
+                    }
+                    Err(e) if is_transient_error(&e) => (anyhow!(e), None),
+                    Err(e) => return Err(e.into()),
+                }
+            };
+
+            if attempt >= retry_policy.max_attempts {
+                return Err(err.context(RetriesExhausted {
+                    url: url.to_string(),
+                    attempts: attempt,
+                }));
+            }
+
+            let delay = retry_policy.delay(attempt, requested_delay);
+            warn!(
+                "Attempt {} to fetch {} failed, retrying in {:?}. Caused by: {}",
+                attempt, url, delay, err
+            );
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Fetches the page of the packages list together with the number of its pages.
+    #[instrument(skip(self))]
+    pub async fn get_packages_list_page(&self, url: &str) -> Result<PackagesListPage> {
         let html_content = self.get_parsed_page(url).await?;
-        scrap_packages_from_page(html_content)
+        let (packages_count, pages_count) = scrap_packages_list_stats(&html_content);
+
+        Ok(PackagesListPage {
+            packages: scrap_packages_from_page(html_content)?,
+            packages_count,
+            pages_count,
+        })
+    }
+
+    /// Scrapes comments from all comment pages of the package, used when the rest of the
+    /// package data comes from elsewhere.
+    #[instrument(skip(self))]
+    pub async fn get_package_comments(&self, url: &str) -> Result<Vec<Comment>> {
+        let (comments, pages) = {
+            let html_content = self.get_parsed_page(url).await?;
+            let comments = scrap_package_comments(&html_content)?;
+            (comments, get_last_comment_page_number(&html_content))
+        };
+
+        self.get_remaining_comments(url, comments, pages).await
     }
 
-    #[allow(unused)]
-    pub async fn get_package_details_from_page(
+    /// Scrapes comments from the comment pages following the first one and returns all of them
+    /// in chronological order. Comment pages which could not be scraped end the walk, so the
+    /// package keeps its details and the newest comments instead of becoming a partial record.
+    async fn get_remaining_comments(
         &self,
         url: &str,
-    ) -> Result<(AdditionalPackageData, Vec<PackageDependency>)> {
-        let html_content = self.get_parsed_page(url).await?;
-        let (additional, dependencies) = scrap_package_details(&html_content)
-            .with_context(|| format!("Failed to scrap details for {}", url))?;
+        mut comments: Vec<Comment>,
+        pages: usize,
+    ) -> Result<Vec<Comment>> {
+        let pages = self.max_comment_pages.map_or(pages, |max| pages.min(max));
+
+        // AUR lists comments from the newest one, so pages are walked from the first one and
+        // the whole list is reversed at the end
+        for page in 1..pages {
+            let page_url = format!("{}?O={}", url, page * COMMENTS_PER_PAGE);
+            let page_comments = self
+                .get_parsed_page(&page_url)
+                .await
+                .and_then(|html_content| scrap_package_comments(&html_content));
+
+            match page_comments {
+                Ok(page_comments) => comments.extend(page_comments),
+                Err(e) => {
+                    warn!(
+                        "Skipping comments older than page {}. Caused by: {:#}",
+                        page_url, e
+                    );
+                    break;
+                }
+            }
+        }
+
+        let mut comments = merge_pinned_comments(comments);
+        comments.reverse();
 
-        Ok((additional, dependencies))
+        Ok(comments)
     }
 
-    #[allow(unused)]
-    pub async fn get_package_comments_from_page(&self, url: &str) -> Result<Vec<Comment>> {
-        let html_content = self.get_parsed_page(url).await?;
-        scrap_package_comments(html_content)
+    /// Returns names of all packages built from the package base. Pages of the bases are
+    /// fetched once and reused for the other split packages of the same base.
+    #[instrument(skip(self))]
+    pub async fn get_package_base_members_from_page(&self, url: &str) -> Result<Vec<String>> {
+        // Split packages scraped concurrently wait for the fetch started by the first of them
+        let base = self
+            .package_bases
+            .lock()
+            .unwrap()
+            .entry(url.to_string())
+            .or_default()
+            .clone();
+
+        let members = base
+            .get_or_try_init(|| async {
+                let html_content = self.get_parsed_page(url).await?;
+                Ok::<_, anyhow::Error>(scrap_package_base_members(&html_content))
+            })
+            .await?;
+
+        Ok(members.clone())
+    }
+
+    /// Links the package with the other split packages built from the same package base.
+    async fn link_split_packages(&self, name: &str, additional: &mut AdditionalPackageData) {
+        let Some(base) = additional.package_base.as_deref() else {
+            return;
+        };
+
+        let url = format!("{}/{}", AUR_PKGBASE_URL, base);
+        match self.get_package_base_members_from_page(&url).await {
+            Ok(members) => {
+                additional.split_packages = members.into_iter().filter(|m| m != name).collect()
+            }
+            Err(e) => warn!(
+                "Cannot link {} with packages of the {} base. Caused by: {:#}",
+                name, base, e
+            ),
+        }
     }
 
     #[instrument(skip(self))]
     pub async fn get_package_details_with_comments_from_page(
         &self,
         url: &str,
-    ) -> Result<(AdditionalPackageData, Vec<PackageDependency>, Vec<Comment>)> {
+    ) -> Result<PackageDetails> {
         debug!("Scraping package details with comments");
-        let html_content = self.get_parsed_page(url).await?;
+        let (mut details, pages, all_reqs_hidden) = {
+            let html_content = self.get_parsed_page(url).await?;
 
-        let (additional, dependencies) = scrap_package_details(&html_content)
-            .with_context(|| format!("Failed to scrap details for {}", url))?;
+            let (additional, dependencies) = scrap_package_details(&html_content)
+                .with_context(|| format!("Failed to scrap details for {}", url))?;
 
-        let comments = scrap_package_comments(html_content)?;
+            let details = PackageDetails {
+                additional,
+                dependencies,
+                required_by: scrap_package_required_by(&html_content),
+                comments: scrap_package_comments(&html_content)?,
+            };
+            let pages = get_last_comment_page_number(&html_content);
+            let all_reqs_hidden = html_content.select(&A_ALL_REQS_SELECTOR).next().is_some();
 
-        Ok((additional, dependencies, comments))
+            (details, pages, all_reqs_hidden)
+        };
+
+        // Heavily required packages list only the first few dependants unless all of them are
+        // requested explicitly
+        if all_reqs_hidden {
+            let html_content = self.get_parsed_page(&format!("{}?all_reqs=1", url)).await?;
+            details.required_by = scrap_package_required_by(&html_content);
+        }
+
+        let comments = std::mem::take(&mut details.comments);
+        details.comments = self.get_remaining_comments(url, comments, pages).await?;
+
+        Ok(details)
     }
 }
 
-#[instrument(skip(scraper))]
-pub async fn get_page_and_scrap_packages(
+/// Scrapes details and comments of the packages listed on the packages list. Packages whose
+/// details could not be scraped are returned as partial records.
+pub async fn scrap_packages(
     scraper: Arc<AurScraper>,
-    url: &str,
-) -> Result<Vec<PackageData>> {
-    let start = Instant::now();
-    let packages_basic_data = scraper.get_packages_basic_data_from_page(url).await?;
-
+    packages_basic_data: Vec<BasicPackageData>,
+) -> Vec<PackageData> {
     let mut set = JoinSet::new();
-    let mut details_and_comments = vec![];
+    // Tasks finish in arbitrary order, so results are stored under the index of the package
+    let mut details_and_comments: Vec<Option<Result<PackageDetails>>> =
+        packages_basic_data.iter().map(|_| None).collect();
 
-    for chunk in packages_basic_data.chunks(30) {
-        for basic in chunk {
-            let url = format!("{}{}", AUR_BASE_URL, basic.path_to_additional_data);
+    for (chunk_idx, chunk) in packages_basic_data.chunks(30).enumerate() {
+        for (idx, basic) in chunk.iter().enumerate() {
+            let idx = chunk_idx * 30 + idx;
+            let url = format!("{}{}", scraper.packages_url, basic.path_to_additional_data);
+            let name = basic.name.clone();
             let scraper = scraper.clone();
 
             set.spawn(async move {
-                let (details, deps, comments) = scraper
-                    .get_package_details_with_comments_from_page(&url)
-                    .await?;
+                let details = scraper
+                    .track_package(async {
+                        let mut details = scraper
+                            .get_package_details_with_comments_from_page(&url)
+                            .await?;
+                        scraper
+                            .link_split_packages(&name, &mut details.additional)
+                            .await;
+                        Ok(details)
+                    })
+                    .await;
 
-                Result::<(AdditionalPackageData, Vec<PackageDependency>, Vec<Comment>)>::Ok((
-                    details, deps, comments,
-                ))
+                (idx, details)
             });
         }
 
         while let Some(task_result) = set.join_next().await {
-            let task_result = task_result.map_err(|e| anyhow!(e)).and_then(|tr| tr);
-
             match task_result {
-                Ok((details, deps, comments)) => {
-                    details_and_comments.push((details, deps, comments))
-                }
+                Ok((idx, details)) => details_and_comments[idx] = Some(details),
                 Err(e) => error!("{}", e),
             }
         }
     }
 
-    let duration = start.elapsed();
-
-    let packages: Vec<PackageData> = packages_basic_data
+    packages_basic_data
         .into_iter()
         .zip(details_and_comments)
-        .map(
-            |(basic, (additional, dependencies, comments))| PackageData {
+        .map(|(basic, details)| match details {
+            Some(Ok(details)) => PackageData {
                 basic,
-                additional,
-                dependencies,
-                comments,
+                additional: Some(details.additional),
+                dependencies: details.dependencies,
+                required_by: details.required_by,
+                comments: details.comments,
+                removed_at: None,
             },
-        )
-        .collect();
-
-    info!("Scraped packages from {} in: {:?}", url, duration);
-
-    Ok(packages)
+            Some(Err(e)) => {
+                error!(
+                    "Saving {} as partial record. Caused by: {:#}",
+                    basic.name, e
+                );
+                PackageData::partial(basic)
+            }
+            None => {
+                error!(
+                    "Saving {} as partial record. Scraping task failed",
+                    basic.name
+                );
+                PackageData::partial(basic)
+            }
+        })
+        .collect()
 }
 
 fn scrap_packages_from_page(html_content: Html) -> Result<Vec<BasicPackageData>> {
@@ -177,8 +522,37 @@ fn scrap_packages_from_page(html_content: Html) -> Result<Vec<BasicPackageData>>
     Ok(packages_basic_data)
 }
 
+/// Scrapes the number of packages found and the number of pages from the text above the
+/// packages list, e.g. `96393 packages found. Page 1 of 386.`
+fn scrap_packages_list_stats(html_content: &Html) -> (Option<usize>, Option<u32>) {
+    let Some(p) = html_content
+        .select(&DIV_PKGLIST_STATS_SELECTOR)
+        .next()
+        .and_then(|div| div.select(&P_SELECTOR).next())
+    else {
+        return (None, None);
+    };
+
+    let text = element_text(p);
+    let words = text
+        .split_whitespace()
+        .map(|word| word.trim_end_matches('.'))
+        .collect::<Vec<_>>();
+    let packages_count = words
+        .windows(2)
+        .find(|pair| pair[1] == "packages")
+        .and_then(|pair| pair[0].parse::<usize>().ok());
+    let pages_count = words
+        .windows(2)
+        .find(|pair| pair[0] == "of")
+        .and_then(|pair| pair[1].parse::<u32>().ok())
+        .or_else(|| packages_count.map(|count| count.div_ceil(PACKAGES_PER_PAGE).max(1) as u32));
+
+    (packages_count, pages_count)
+}
+
 fn scrap_package_basic_data(tr: String) -> Result<BasicPackageData> {
-    let tr_fragment = Html::parse_fragment(&tr.trim());
+    let tr_fragment = Html::parse_fragment(tr.trim());
     let mut package_basic_info = vec![];
 
     for td in tr_fragment.select(&TD_SELECTOR) {
@@ -196,8 +570,9 @@ fn scrap_package_basic_data(tr: String) -> Result<BasicPackageData> {
 fn scrap_package_details(
     package_details: &Html,
 ) -> Result<(AdditionalPackageData, Vec<PackageDependency>)> {
-    let additional = scrap_package_additional_data(package_details)
+    let mut additional = scrap_package_additional_data(package_details)
         .with_context(|| "Failed to scrap additional data for package".to_string())?;
+    additional.sources = scrap_package_sources(package_details);
 
     let dependencies = scrap_package_dependencies(package_details)
         .with_context(|| "Failed to scrap dependencies for package".to_string())?;
@@ -239,7 +614,53 @@ fn scrap_package_additional_data(html_content: &Html) -> Result<AdditionalPackag
         }
     }
 
-    AdditionalPackageData::try_from(package_data).map_err(|err| anyhow!(err))
+    // Co-maintainers are listed in parentheses after the maintainer
+    if let Some(maintainers) = package_data.remove("maintainer") {
+        if let Some((_, co_maintainers)) = maintainers.split_once(',') {
+            package_data.insert("comaintainers".into(), co_maintainers.to_string());
+        }
+    }
+
+    // The flag is shown in the package actions as "Flagged out-of-date (YYYY-MM-DD)"
+    if let Some(flagged) = html_content.select(&SPAN_FLAGGED_SELECTOR).next() {
+        let text = element_text(flagged);
+        if let Some(date) = text
+            .rsplit_once('(')
+            .and_then(|(_, date)| date.strip_suffix(')'))
+        {
+            package_data.insert("outofdate".into(), date.trim().to_string());
+        }
+    }
+
+    // Both are already known from the packages list
+    package_data.remove("description");
+    package_data.remove("lastupdated");
+
+    let additional =
+        AdditionalPackageData::from_fields(&mut package_data).map_err(|err| anyhow!(err))?;
+
+    for key in package_data.keys() {
+        warn!("Unknown field {} in the package details", key);
+    }
+
+    Ok(additional)
+}
+
+/// Tells whether the dependency is an AUR package from its link. Official packages link to
+/// archlinux.org and AUR ones to their package page, while virtual dependencies link to the
+/// search of their providers, so their origin is unknown.
+fn dependency_origin(href: &str) -> Option<bool> {
+    let url = Url::parse(AUR_BASE_URL).ok()?.join(href).ok()?;
+
+    match url.host_str()? {
+        "aur.archlinux.org" => {
+            let name = url.path().strip_prefix("/packages/")?;
+            let is_package_page = !name.is_empty() && !name.contains('/') && url.query().is_none();
+            is_package_page.then_some(true)
+        }
+        "archlinux.org" => Some(false),
+        _ => None,
+    }
 }
 
 fn scrap_package_dependencies(html_content: &Html) -> Result<Vec<PackageDependency>> {
@@ -248,16 +669,57 @@ fn scrap_package_dependencies(html_content: &Html) -> Result<Vec<PackageDependen
     for ul in html_content.select(&UL_DEPS_SELECTOR) {
         for li in ul.select(&LI_SELECTOR) {
             if let Some(a) = li.select(&A_SELECTOR).next() {
-                let group = a.inner_html().trim().to_string();
+                let name = element_text(a);
+                let is_aur = dependency_origin(&extract_attribute_value(a, "href"));
+
+                // The version constraint is the only text placed directly in the list item
+                let constraint_text = li
+                    .children()
+                    .filter_map(|child| child.value().as_text().map(|t| t.trim()))
+                    .collect::<String>();
+                let constraint = match constraint_text.parse() {
+                    Ok(constraint) => Some(constraint),
+                    Err(_) if constraint_text.is_empty() => None,
+                    Err(e) => {
+                        warn!("Cannot parse constraint of the {} dependency: {}", name, e);
+                        None
+                    }
+                };
 
-                let mut packages = vec![];
+                let mut kind = DependencyKind::Depends;
+                let mut description = None;
+                let mut providers = vec![];
+
+                // Providers of virtual dependencies and the dependency kind are both emphasized
                 for em in li.select(&EM_SELECTOR) {
-                    for a in em.select(&A_SELECTOR) {
-                        packages.push(a.inner_html().trim().to_string());
+                    if em.select(&A_SELECTOR).next().is_some() {
+                        providers.extend(em.select(&A_SELECTOR).map(element_text));
+                        continue;
                     }
+
+                    let extra = element_text(em);
+                    let (marker, desc) = match extra.split_once('–') {
+                        Some((marker, desc)) => (marker.trim(), Some(desc.trim())),
+                        None => (extra.trim(), None),
+                    };
+
+                    kind = match marker {
+                        "(make)" => DependencyKind::MakeDepends,
+                        "(check)" => DependencyKind::CheckDepends,
+                        "(optional)" => DependencyKind::OptDepends,
+                        _ => kind,
+                    };
+                    description = desc.filter(|d| !d.is_empty()).map(|d| d.to_string());
                 }
 
-                dependencies.push(PackageDependency { group, packages });
+                dependencies.push(PackageDependency {
+                    name,
+                    kind,
+                    constraint,
+                    description,
+                    is_aur,
+                    providers,
+                });
             }
         }
     }
@@ -265,42 +727,1034 @@ fn scrap_package_dependencies(html_content: &Html) -> Result<Vec<PackageDependen
     Ok(dependencies)
 }
 
-fn scrap_package_comments(html_content: Html) -> Result<Vec<Comment>> {
+fn scrap_package_sources(html_content: &Html) -> Vec<Source> {
+    html_content
+        .select(&UL_SRCS_SELECTOR)
+        .flat_map(|ul| ul.select(&LI_SELECTOR))
+        .filter_map(|li| li.select(&A_SELECTOR).next())
+        .map(|a| Source {
+            filename: element_text(a),
+            url: extract_attribute_value(a, "href"),
+        })
+        .collect()
+}
+
+fn scrap_package_base_members(html_content: &Html) -> Vec<String> {
+    html_content
+        .select(&DIV_PKGBASE_PKGS_SELECTOR)
+        .flat_map(|div| div.select(&LI_SELECTOR))
+        .filter_map(|li| li.select(&A_SELECTOR).next())
+        .map(element_text)
+        .collect()
+}
+
+fn parse_comment_timestamp(a: ElementRef, field: &'static str) -> Option<DateTime<Utc>> {
+    parse_displayed_timestamp(&element_text(a), field)
+        .map_err(|e| warn!("{:#}", anyhow!(e)))
+        .ok()
+}
+
+/// Parses the author from the comment header, e.g. `alice commented on 2023-03-04 12:30 (UTC)`.
+fn parse_comment_author(header: &str) -> Option<String> {
+    header
+        .split_once(" commented on ")
+        .map(|(author, _)| author.trim().to_string())
+        .filter(|author| !author.is_empty())
+}
+
+/// Parses the time and the editor from the edit note of the comment, e.g.
+/// `(edited on 2023-03-05 08:00 (UTC) by bob)`.
+fn parse_comment_edit(note: &str) -> (Option<DateTime<Utc>>, Option<String>) {
+    let Some((edited_at, edited_by)) = note
+        .trim()
+        .trim_start_matches('(')
+        .strip_suffix(')')
+        .and_then(|note| note.trim().strip_prefix("edited on "))
+        .and_then(|note| note.rsplit_once(" by "))
+    else {
+        warn!("Cannot parse comment edit note {}", note);
+        return (None, None);
+    };
+
+    let edited_at = parse_displayed_timestamp(edited_at, "edited_at")
+        .map_err(|e| warn!("{:#}", anyhow!(e)))
+        .ok();
+
+    (edited_at, Some(edited_by.trim().to_string()))
+}
+
+/// Returns names of the packages which depend on the scraped one.
+fn scrap_package_required_by(html_content: &Html) -> Vec<String> {
+    html_content
+        .select(&UL_REQS_SELECTOR)
+        .flat_map(|ul| ul.select(&LI_SELECTOR))
+        .filter_map(|li| li.select(&A_SELECTOR).next())
+        .map(element_text)
+        .collect()
+}
+
+fn scrap_package_comments(html_content: &Html) -> Result<Vec<Comment>> {
     let mut comments = vec![];
 
     for comments_container in html_content.select(&DIV_COMMENTS_SELECTOR) {
+        // Pinned comments are listed in a separate container above the latest comments
+        let is_pinned = comments_container
+            .select(&H3_SELECTOR)
+            .next()
+            .is_some_and(|h3| h3.text().any(|t| t.contains("Pinned")));
+
         for (comment_header, comment_content) in comments_container
             .select(&H4_COMMENT_HEADER_SELECTOR)
             .zip(comments_container.select(&DIV_COMMENT_CONTENT_SELECTOR))
-            // Skip pinned comment
-            .skip(1)
         {
-            let header = delete_tags(comment_header.inner_html());
-            let content = delete_tags(comment_content.inner_html());
+            let header = element_text(comment_header);
+            let content = to_markdown(comment_content);
 
-            comments.push(Comment { header, content })
+            let id = comment_header
+                .value()
+                .attr("id")
+                .and_then(|id| id.strip_prefix("comment-"))
+                .and_then(|id| id.parse().ok());
+            // Account links are shown to logged in users only, so names are read from the text
+            let author = parse_comment_author(&header);
+            let posted_at = comment_header
+                .select(&A_DATE_SELECTOR)
+                .next()
+                .and_then(|a| parse_comment_timestamp(a, "posted_at"));
+            let (edited_at, edited_by) = comment_header
+                .select(&SPAN_EDITED_SELECTOR)
+                .next()
+                .map_or((None, None), |span| parse_comment_edit(&element_text(span)));
+
+            comments.push(Comment {
+                header,
+                content,
+                is_pinned,
+                id,
+                author,
+                posted_at,
+                edited_at,
+                edited_by,
+            })
         }
     }
 
     Ok(comments)
 }
 
-#[allow(unused)]
-fn get_last_comment_page_number(html_content: Html) -> usize {
+/// Pinned comments are repeated on every comment page and they are also present in the latest
+/// comments, so they are merged into the regular comments stream. Pinned comments which are not
+/// in the scraped part of the stream are older than it, hence they are put at its end.
+fn merge_pinned_comments(comments: Vec<Comment>) -> Vec<Comment> {
+    let (pinned, mut regular): (Vec<Comment>, Vec<Comment>) =
+        comments.into_iter().partition(|c| c.is_pinned);
+
+    let mut older_pinned: Vec<Comment> = vec![];
+
+    for comment in pinned {
+        let same = |c: &&mut Comment| match (c.id, comment.id) {
+            (Some(id), Some(other_id)) => id == other_id,
+            _ => c.header == comment.header && c.content == comment.content,
+        };
+
+        if let Some(regular_comment) = regular.iter_mut().find(same) {
+            regular_comment.is_pinned = true;
+        } else if !older_pinned.iter_mut().any(|c| same(&c)) {
+            older_pinned.push(comment);
+        }
+    }
+
+    regular.extend(older_pinned);
+    regular
+}
+
+fn get_last_comment_page_number(html_content: &Html) -> usize {
     let comment_nav = html_content.select(&P_COMMENT_HEADER_NAV_SELECTOR).next();
 
     // Case when there is only one comment page
-    let Some(comment_nav) = comment_nav else { return 1; };
+    let Some(comment_nav) = comment_nav else {
+        return 1;
+    };
 
-    let comment_pages = comment_nav
+    // Link to the last page is the last one and it points to the offset of its first comment
+    let last_offset = comment_nav
         .select(&A_PAGE_SELECTOR)
-        .collect::<Vec<ElementRef>>();
-    let next_a = comment_pages.iter().last().unwrap();
-
-    extract_attribute_value(*next_a, "href")
-        .split('=')
         .last()
-        .unwrap()
-        .parse::<usize>()
-        .unwrap()
+        .map(|a| extract_attribute_value(a, "href"))
+        .and_then(|href| {
+            href.split(['?', '&', '#'])
+                .find_map(|param| param.strip_prefix("O="))
+                .and_then(|offset| offset.parse::<usize>().ok())
+        });
+
+    match last_offset {
+        Some(offset) => offset / COMMENTS_PER_PAGE + 1,
+        None => {
+            warn!("Cannot find the last comment page, scraping only the first one");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{
+        dependency_origin, scrap_package_basic_data, scrap_package_comments,
+        scrap_package_dependencies, scrap_package_details, scrap_packages,
+        scrap_packages_list_stats, AurScraper, PackagesListPage, PackagesListQuery, ScraperConfig,
+        AUR_PKGBASE_URL,
+    };
+    use crate::{
+        cli::ListSearchBy,
+        database::shared::create_package_data,
+        models::{Comment, ConstraintOperator, DependencyKind},
+        retry::RetryPolicy,
+        stub_server::{StubResponse, StubServer},
+    };
+    use anyhow::Result;
+    use chrono::{TimeZone, Utc};
+    use clap::ValueEnum;
+    use scraper::Html;
+    use tokio::{sync::OnceCell, task::JoinSet, time::Instant};
+
+    fn package_page(pinned: &[&str], comments: &[&str], last_offset: Option<usize>) -> String {
+        let nav = last_offset.map_or(String::new(), |offset| {
+            format!(
+                r#"<p class="comments-header-nav"><a class="page" href="/packages/pkg?O=10">›</a><a class="page" href="/packages/pkg?O={}">Last »</a></p>"#,
+                offset
+            )
+        });
+        let to_html = |comments: &[&str]| {
+            comments
+                .iter()
+                .map(|c| {
+                    format!(
+                        r#"<h4 class="comment-header">tester commented</h4><div class="article-content"><p>{}</p></div>"#,
+                        c
+                    )
+                })
+                .collect::<String>()
+        };
+
+        format!(
+            r#"<html><body>
+            <table id="pkginfo"><tbody>
+                <tr><th>Git Clone URL:</th><td><a href="https://aur.archlinux.org/pkg.git">https://aur.archlinux.org/pkg.git</a></td></tr>
+                <tr><th>Submitter:</th><td>tester</td></tr>
+                <tr><th>First Submitted:</th><td>2020-01-01 10:00 (UTC)</td></tr>
+            </tbody></table>
+            <div class="comments package-comments">
+                <div class="comments-header"><h3><span class="text">Pinned Comments</span></h3></div>
+                {}
+            </div>
+            <div class="comments package-comments">
+                <div class="comments-header"><h3><span class="text">Latest Comments</span></h3>{}</div>
+                {}
+            </div>
+            </body></html>"#,
+            to_html(pinned),
+            nav,
+            to_html(comments)
+        )
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limit_is_respected() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::from_millis(50), |_| {
+            StubResponse::ok("<html></html>")
+        })
+        .await;
+        let scraper = Arc::new(AurScraper::new(ScraperConfig {
+            requests_per_second: 20.0,
+            max_in_flight: 3,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        }));
+        let mut set = JoinSet::new();
+
+        // Act
+        let start = Instant::now();
+        for i in 0..30 {
+            let scraper = scraper.clone();
+            let url = server.url(&format!("/packages/pkg{}", i));
+            set.spawn(async move { scraper.get_parsed_page(&url).await.map(|_| ()) });
+        }
+        while let Some(res) = set.join_next().await {
+            res??;
+        }
+        let elapsed = start.elapsed();
+
+        // Assert
+        assert_eq!(server.served(), 30);
+        assert!(server.max_in_flight() <= 3);
+        // The bucket starts with a single token, so 29 requests have to wait for a refill
+        assert!(elapsed >= Duration::from_secs_f64(29.0 / 20.0));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retry_transient_failures_honouring_retry_after() -> Result<()> {
+        // Arrange
+        let calls = AtomicUsize::new(0);
+        let server = StubServer::start(Duration::ZERO, move |_| {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => StubResponse {
+                    status: 503,
+                    headers: vec![("Retry-After".into(), "1".into())],
+                    body: String::new(),
+                },
+                1 => StubResponse {
+                    status: 500,
+                    headers: vec![],
+                    body: String::new(),
+                },
+                _ => StubResponse::ok("<html></html>"),
+            }
+        })
+        .await;
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: RetryPolicy {
+                max_delay: Duration::from_secs(2),
+                ..retry_policy()
+            },
+            max_comment_pages: None,
+        });
+
+        // Act
+        let start = Instant::now();
+        let page = scraper
+            .track_package(scraper.get_parsed_page(&server.url("/packages/pkg")))
+            .await;
+
+        // Assert
+        assert!(page.is_ok());
+        assert_eq!(server.served(), 3);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(scraper.attempts_summary().contains("3 attempt(s): 1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cap_retry_after_at_max_delay() -> Result<()> {
+        // Arrange
+        let calls = AtomicUsize::new(0);
+        let server = StubServer::start(Duration::ZERO, move |_| {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => StubResponse {
+                    status: 429,
+                    headers: vec![("Retry-After".into(), "86400".into())],
+                    body: String::new(),
+                },
+                _ => StubResponse::ok("<html></html>"),
+            }
+        })
+        .await;
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        });
+
+        // Act
+        let start = Instant::now();
+        let page = scraper.get_parsed_page(&server.url("/packages/pkg")).await;
+
+        // Assert
+        assert!(page.is_ok());
+        assert_eq!(server.served(), 2);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_attempts_and_on_client_errors() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::ZERO, |path| StubResponse {
+            status: if path.ends_with("missing") { 404 } else { 502 },
+            headers: vec![],
+            body: String::new(),
+        })
+        .await;
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        });
+
+        // Act
+        let unavailable = scraper
+            .track_package(scraper.get_parsed_page(&server.url("/packages/pkg")))
+            .await;
+        let missing = scraper
+            .track_package(scraper.get_parsed_page(&server.url("/packages/missing")))
+            .await;
+
+        // Assert
+        assert!(unavailable.is_err());
+        assert!(missing.is_err());
+        assert_eq!(server.served(), 4);
+        assert!(scraper
+            .attempts_summary()
+            .ends_with("failed after all attempts: 1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn walk_comment_pages_in_chronological_order_with_pinned_comments() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::ZERO, |path| {
+            let page = match path.split_once("?O=") {
+                None => package_page(&["old", "ancient"], &["newest", "newer"], Some(20)),
+                Some((_, "10")) => package_page(&["old", "ancient"], &["older", "old"], Some(20)),
+                Some(_) => package_page(&["old", "ancient"], &["oldest"], Some(20)),
+            };
+            StubResponse::ok(page)
+        })
+        .await;
+        let scraper = |max_comment_pages| {
+            AurScraper::new(ScraperConfig {
+                requests_per_second: 100.0,
+                max_in_flight: 1,
+                retry: retry_policy(),
+                max_comment_pages,
+            })
+        };
+
+        // Act
+        let all = scraper(None)
+            .get_package_details_with_comments_from_page(&server.url("/packages/pkg"))
+            .await?
+            .comments;
+        let limited = scraper(Some(2))
+            .get_package_details_with_comments_from_page(&server.url("/packages/pkg"))
+            .await?
+            .comments;
+
+        // Assert
+        let contents = |comments: &[Comment]| {
+            comments
+                .iter()
+                .map(|c| format!("{}{}", c.content, if c.is_pinned { "*" } else { "" }))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            contents(&all),
+            vec!["ancient*", "oldest", "old*", "older", "newer", "newest"]
+        );
+        assert_eq!(
+            contents(&limited),
+            vec!["ancient*", "old*", "older", "newer", "newest"]
+        );
+        assert_eq!(server.served(), 5);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_details_and_newer_comments_when_comment_page_fails() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::ZERO, |path| match path.split_once("?O=") {
+            None => StubResponse::ok(package_page(&["old"], &["newest", "newer"], Some(20))),
+            Some((_, "10")) => StubResponse {
+                status: 500,
+                headers: vec![],
+                body: String::new(),
+            },
+            Some(_) => StubResponse::ok(package_page(&["old"], &["oldest"], Some(20))),
+        })
+        .await;
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        });
+
+        // Act
+        let details = scraper
+            .get_package_details_with_comments_from_page(&server.url("/packages/pkg"))
+            .await?;
+
+        // Assert
+        assert_eq!(details.additional.submitter, "tester");
+        let contents = details
+            .comments
+            .iter()
+            .map(|c| c.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["old", "newer", "newest"]);
+        assert_eq!(server.served(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetch_all_dependants_when_list_is_truncated() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::ZERO, |path| {
+            let reqs = |names: &[&str], more: bool| {
+                let items = names
+                    .iter()
+                    .map(|n| {
+                        format!(
+                            r#"<li><a href="/packages/{0}">{0}</a> <em>(make)</em></li>"#,
+                            n
+                        )
+                    })
+                    .collect::<String>();
+                let more = if more {
+                    r#"<a href="/packages/pkg?all_reqs=1#pkgreqs">Show 1 more...</a>"#
+                } else {
+                    ""
+                };
+                format!(
+                    r#"<div id="pkgreqs"><ul id="pkgreqslist">{}</ul>{}</div>"#,
+                    items, more
+                )
+            };
+            let page = package_page(&[], &[], None);
+            let page = match path.ends_with("all_reqs=1") {
+                false => page.replace("<body>", &format!("<body>{}", reqs(&["a", "b"], true))),
+                true => page.replace(
+                    "<body>",
+                    &format!("<body>{}", reqs(&["a", "b", "c"], false)),
+                ),
+            };
+            StubResponse::ok(page)
+        })
+        .await;
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        });
+
+        // Act
+        let details = scraper
+            .get_package_details_with_comments_from_page(&server.url("/packages/pkg"))
+            .await?;
+
+        // Assert
+        assert_eq!(details.required_by, vec!["a", "b", "c"]);
+        assert_eq!(server.served(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_sources_and_package_base() -> Result<()> {
+        // Arrange
+        let page = package_page(&[], &[], None).replace(
+            "</tbody></table>",
+            r#"<tr><th>Package Base:</th><td><a href="/pkgbase/pkg-base">pkg-base</a></td></tr>
+            </tbody></table>
+            <div id="pkgsrcs"><ul id="pkgsrcslist">
+                <li><a href="https://example.com/pkg-1.0.tar.gz">pkg-1.0.tar.gz</a></li>
+                <li><a href="https://aur.archlinux.org/cgit/aur.git/tree/fix.patch?h=pkg-base">fix.patch</a></li>
+            </ul></div>"#,
+        );
+        let html = Html::parse_document(&page);
+
+        // Act
+        let (additional, _) = scrap_package_details(&html)?;
+
+        // Assert
+        assert_eq!(additional.package_base.as_deref(), Some("pkg-base"));
+        assert_eq!(additional.sources.len(), 2);
+        assert_eq!(additional.sources[0].filename, "pkg-1.0.tar.gz");
+        assert_eq!(
+            additional.sources[0].url,
+            "https://example.com/pkg-1.0.tar.gz"
+        );
+        assert_eq!(additional.sources[1].filename, "fix.patch");
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_remaining_pkginfo_fields() -> Result<()> {
+        // Arrange
+        let html = Html::parse_document(
+            r#"<div id="actionlist"><ul class="small">
+                <li><span class="flagged">Flagged out-of-date (2023-04-05)</span></li>
+            </ul></div>
+            <table id="pkginfo"><tbody>
+                <tr><th>Git Clone URL:</th><td><a href="https://aur.archlinux.org/pkg.git">https://aur.archlinux.org/pkg.git</a></td></tr>
+                <tr><th>Description:</th><td>Test package</td></tr>
+                <tr><th>Upstream URL:</th><td><a href="https://example.com">https://example.com</a></td></tr>
+                <tr><th>Groups:</th><td>tools, tests</td></tr>
+                <tr><th>Replaces:</th><td>old-pkg</td></tr>
+                <tr><th>Submitter:</th><td><a href="/account/alice">alice</a></td></tr>
+                <tr><th>Maintainer:</th><td><a href="/account/alice">alice</a> (<a href="/account/bob">bob</a>, <a href="/account/carol">carol</a>)</td></tr>
+                <tr><th>Last Packager:</th><td><a href="/account/bob">bob</a></td></tr>
+                <tr><th>Votes:</th><td>42</td></tr>
+                <tr><th>Popularity:</th><td>1.25</td></tr>
+                <tr><th>First Submitted:</th><td>2020-01-01 10:00 (UTC)</td></tr>
+                <tr><th>Last Updated:</th><td>2023-01-01 10:00 (UTC)</td></tr>
+            </tbody></table>"#,
+        );
+
+        // Act
+        let (additional, _) = scrap_package_details(&html)?;
+
+        // Assert
+        assert_eq!(
+            additional.upstream_url.as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(additional.groups, vec!["tools", "tests"]);
+        assert_eq!(additional.replaces, vec!["old-pkg"]);
+        assert_eq!(additional.co_maintainers, vec!["bob", "carol"]);
+        assert_eq!(additional.last_packager.as_deref(), Some("bob"));
+        assert_eq!(
+            additional.out_of_date,
+            Some(Utc.with_ymd_and_hms(2023, 4, 5, 0, 0, 0).unwrap())
+        );
+        assert_eq!(additional.votes, Some(42));
+        assert_eq!(additional.popularity, Some(1.25));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetch_package_base_members_once() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::ZERO, |_| {
+            StubResponse::ok(
+                r#"<div id="pkgs" class="listing"><h3>Packages (2)</h3><ul>
+                <li><a href="/packages/pkg">pkg</a></li>
+                <li><a href="/packages/pkg-docs">pkg-docs</a></li>
+                </ul></div>"#,
+            )
+        })
+        .await;
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        });
+        let url = server.url("/pkgbase/pkg");
+
+        // Act
+        let first = scraper.get_package_base_members_from_page(&url).await?;
+        let second = scraper.get_package_base_members_from_page(&url).await?;
+
+        // Assert
+        assert_eq!(first, vec!["pkg", "pkg-docs"]);
+        assert_eq!(first, second);
+        assert_eq!(server.served(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetch_package_base_members_once_for_concurrent_split_packages() -> Result<()> {
+        // Arrange
+        let server = StubServer::start(Duration::from_millis(50), |_| {
+            StubResponse::ok(
+                r#"<div id="pkgs" class="listing"><h3>Packages (2)</h3><ul>
+                <li><a href="/packages/pkg">pkg</a></li>
+                <li><a href="/packages/pkg-docs">pkg-docs</a></li>
+                </ul></div>"#,
+            )
+        })
+        .await;
+        let scraper = Arc::new(AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 4,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        }));
+        let mut set = JoinSet::new();
+
+        // Act
+        for _ in 0..4 {
+            let scraper = scraper.clone();
+            let url = server.url("/pkgbase/pkg");
+            set.spawn(async move { scraper.get_package_base_members_from_page(&url).await });
+        }
+        let mut results = vec![];
+        while let Some(res) = set.join_next().await {
+            results.push(res??);
+        }
+
+        // Assert
+        assert!(results
+            .iter()
+            .all(|members| members == &["pkg", "pkg-docs"]));
+        assert_eq!(server.served(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pair_details_with_their_packages_and_keep_failed_as_partial() -> Result<()> {
+        // Arrange
+        let calls = AtomicUsize::new(0);
+        let server = StubServer::start(Duration::ZERO, move |path| match path {
+            // The first package finishes last, after its retries
+            "/pkg0" if calls.fetch_add(1, Ordering::SeqCst) < 2 => StubResponse {
+                status: 500,
+                headers: vec![],
+                body: String::new(),
+            },
+            "/pkg1" => StubResponse {
+                status: 404,
+                headers: vec![],
+                body: String::new(),
+            },
+            _ => StubResponse::ok(package_page(&[], &[&format!("About {}", &path[1..])], None)),
+        })
+        .await;
+        let scraper = Arc::new(
+            AurScraper::new(ScraperConfig {
+                requests_per_second: 100.0,
+                max_in_flight: 3,
+                retry: retry_policy(),
+                max_comment_pages: None,
+            })
+            .with_packages_url(&server.url("")),
+        );
+        let packages_basic_data = (0..3)
+            .map(|i| {
+                let mut basic = create_package_data().basic;
+                basic.name = format!("pkg{}", i);
+                basic.path_to_additional_data = format!("/pkg{}", i);
+                basic
+            })
+            .collect();
+
+        // Act
+        let packages = scrap_packages(scraper, packages_basic_data).await;
+
+        // Assert
+        let names = packages
+            .iter()
+            .map(|pkg| pkg.basic.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["pkg0", "pkg1", "pkg2"]);
+        assert_eq!(packages[0].comments[0].content, "About pkg0");
+        assert!(packages[1].is_partial());
+        assert!(packages[1].comments.is_empty());
+        assert_eq!(packages[2].comments[0].content, "About pkg2");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn link_package_named_after_its_base() -> Result<()> {
+        // Arrange
+        let scraper = AurScraper::new(ScraperConfig {
+            requests_per_second: 100.0,
+            max_in_flight: 1,
+            retry: retry_policy(),
+            max_comment_pages: None,
+        });
+        scraper.package_bases.lock().unwrap().insert(
+            format!("{}/foo", AUR_PKGBASE_URL),
+            Arc::new(OnceCell::new_with(Some(vec![
+                "foo".into(),
+                "foo-docs".into(),
+            ]))),
+        );
+        let mut additional = create_package_data().additional.unwrap();
+        additional.package_base = Some("foo".into());
+        additional.split_packages = vec![];
+
+        // Act
+        scraper.link_split_packages("foo", &mut additional).await;
+
+        // Assert
+        assert_eq!(additional.split_packages, vec!["foo-docs"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_structured_comment_header() -> Result<()> {
+        // Arrange
+        let html = Html::parse_document(
+            r##"<div class="comments package-comments">
+            <h4 id="comment-912345" class="comment-header">
+                <a href="/account/alice" title="View account information for alice">alice</a>
+                commented on
+                <a href="#comment-912345" class="date">2023-03-04 12:30 (UTC)</a>
+                <span class="edited">
+                    (edited on <a href="#comment-912345" class="date">2023-03-05 08:00 (UTC)</a>
+                    by <a href="/account/bob" title="View account information for bob">bob</a>)
+                </span>
+            </h4>
+            <div id="comment-912345-content" class="article-content"><div><p>Works fine</p></div></div>
+            </div>"##,
+        );
+
+        // Act
+        let comments = scrap_package_comments(&html)?;
+
+        // Assert
+        let comment = &comments[0];
+        assert_eq!(comment.id, Some(912345));
+        assert_eq!(comment.author.as_deref(), Some("alice"));
+        assert_eq!(
+            comment.posted_at,
+            Some(Utc.with_ymd_and_hms(2023, 3, 4, 12, 30, 0).unwrap())
+        );
+        assert_eq!(
+            comment.edited_at,
+            Some(Utc.with_ymd_and_hms(2023, 3, 5, 8, 0, 0).unwrap())
+        );
+        assert_eq!(comment.edited_by.as_deref(), Some("bob"));
+        assert!(comment.header.starts_with("alice"));
+        assert!(!comment.is_pinned);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_comment_headers_shown_to_anonymous_visitors() -> Result<()> {
+        // Arrange
+        let html = Html::parse_document(include_str!("../tests/fixtures/comments_anonymous.html"));
+
+        // Act
+        let comments = scrap_package_comments(&html)?;
+
+        // Assert
+        assert_eq!(comments.len(), 3);
+        assert!(comments[0].is_pinned);
+        assert_eq!(comments[0].author.as_deref(), Some("carol"));
+
+        let edited = &comments[1];
+        assert_eq!(edited.id, Some(912345));
+        assert_eq!(edited.author.as_deref(), Some("alice"));
+        assert_eq!(
+            edited.posted_at,
+            Some(Utc.with_ymd_and_hms(2023, 3, 4, 12, 30, 0).unwrap())
+        );
+        assert_eq!(
+            edited.edited_at,
+            Some(Utc.with_ymd_and_hms(2023, 3, 5, 8, 0, 0).unwrap())
+        );
+        assert_eq!(edited.edited_by.as_deref(), Some("bob"));
+
+        let not_edited = &comments[2];
+        assert_eq!(not_edited.author.as_deref(), Some("carol"));
+        assert!(not_edited.edited_at.is_none());
+        assert!(not_edited.edited_by.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_dependency_kinds_constraints_and_providers() -> Result<()> {
+        // Arrange
+        let html = Html::parse_fragment(
+            r#"<ul id="pkgdepslist">
+            <li><a href="https://archlinux.org/packages/?q=python">python</a>&gt;=3.9</li>
+            <li><a href="/packages/libfoo-git">libfoo-git</a></li>
+            <li><a href="/packages/?K=java-runtime&amp;SeB=p">java-runtime</a>
+                <em>(<a href="https://archlinux.org/packages/?q=jre-openjdk">jre-openjdk</a>, <a href="/packages/jre-zulu">jre-zulu</a>)</em>
+            </li>
+            <li><a href="https://archlinux.org/packages/?q=cmake">cmake</a> <em>(make)</em></li>
+            <li><a href="https://archlinux.org/packages/?q=gtest">gtest</a>=1.13.0 <em>(check)</em></li>
+            <li><a href="https://archlinux.org/packages/?q=gtk3">gtk3</a> <em>(optional) &ndash; for the GUI</em></li>
+            </ul>"#,
+        );
+
+        // Act
+        let deps = scrap_package_dependencies(&html)?;
+
+        // Assert
+        assert_eq!(deps.len(), 6);
+
+        assert_eq!(deps[0].name, "python");
+        assert_eq!(deps[0].kind, DependencyKind::Depends);
+        let constraint = deps[0].constraint.as_ref().unwrap();
+        assert_eq!(constraint.operator, ConstraintOperator::GreaterOrEqual);
+        assert_eq!(constraint.version, "3.9");
+        assert_eq!(deps[0].is_aur, Some(false));
+
+        assert_eq!(deps[1].name, "libfoo-git");
+        assert!(deps[1].constraint.is_none());
+        assert_eq!(deps[1].is_aur, Some(true));
+
+        assert_eq!(deps[2].name, "java-runtime");
+        assert_eq!(deps[2].providers, vec!["jre-openjdk", "jre-zulu"]);
+        assert_eq!(deps[2].kind, DependencyKind::Depends);
+        assert_eq!(deps[2].is_aur, None);
+
+        assert_eq!(deps[3].kind, DependencyKind::MakeDepends);
+
+        assert_eq!(deps[4].kind, DependencyKind::CheckDepends);
+        assert_eq!(deps[4].constraint.as_ref().unwrap().to_string(), "=1.13.0");
+
+        assert_eq!(deps[5].kind, DependencyKind::OptDepends);
+        assert_eq!(deps[5].description.as_deref(), Some("for the GUI"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tell_dependency_origin_from_link() {
+        // Act & Assert
+        assert_eq!(dependency_origin("/packages/libfoo-git"), Some(true));
+        assert_eq!(
+            dependency_origin("https://aur.archlinux.org/packages/libfoo-git"),
+            Some(true)
+        );
+        assert_eq!(
+            dependency_origin("https://archlinux.org/packages/?q=python"),
+            Some(false)
+        );
+        assert_eq!(dependency_origin("/packages/?K=java-runtime&SeB=p"), None);
+        assert_eq!(dependency_origin("/packages/"), None);
+    }
+
+    #[test]
+    fn parse_packages_list_row() -> Result<()> {
+        // Arrange
+        let tr = format!(
+            "<table><tbody>{}<tbody><table>",
+            include_str!("../tests/fixtures/packages_list_row.html")
+        );
+
+        // Act
+        let basic = scrap_package_basic_data(tr)?;
+
+        // Assert
+        assert_eq!(basic.name, "google-chrome");
+        assert_eq!(basic.path_to_additional_data, "/google-chrome");
+        assert_eq!(basic.version, "119.0.6045.105-1");
+        assert_eq!(basic.votes, 1927);
+        assert_eq!(basic.maintainer, "gromit");
+        assert_eq!(
+            basic.last_updated,
+            Utc.with_ymd_and_hms(2023, 10, 31, 0, 0, 0).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_number_of_packages_and_pages() {
+        // Arrange
+        let stats = |text: &str| {
+            Html::parse_document(&format!(
+                r#"<div class="pkglist-stats"><p>{}</p><p class="pkglist-nav"></p></div>"#,
+                text
+            ))
+        };
+
+        // Act
+        let with_pages = scrap_packages_list_stats(&stats(
+            "\n        96393 packages found.\n        Page 1 of 386.\n    ",
+        ));
+        let without_pages = scrap_packages_list_stats(&stats("501 packages found."));
+        let missing = scrap_packages_list_stats(&Html::parse_document("<html></html>"));
+
+        // Assert
+        assert_eq!(with_pages, (Some(96393), Some(386)));
+        assert_eq!(without_pages, (Some(501), Some(3)));
+        assert_eq!(missing, (None, None));
+    }
+
+    #[test]
+    fn build_urls_of_packages_list_pages() {
+        // Arrange
+        let popular = PackagesListQuery::default();
+        let newest_git = PackagesListQuery {
+            search_by: "n",
+            keywords: "-git qt5".into(),
+            sort_by: "l",
+            sort_order: "d",
+        };
+        let orphans = PackagesListQuery {
+            search_by: "m",
+            ..PackagesListQuery::default()
+        };
+
+        // Act
+        let popular_url = popular.page_url(1);
+        let newest_git_url = newest_git.page_url(3);
+
+        // Assert
+        assert_eq!(
+            popular_url,
+            "https://aur.archlinux.org/packages?PP=250&SeB=nd&K=&SB=p&SO=d&O=0"
+        );
+        assert_eq!(
+            newest_git_url,
+            "https://aur.archlinux.org/packages?PP=250&SeB=n&K=-git+qt5&SB=l&SO=d&O=500"
+        );
+        assert_eq!(
+            PackagesListQuery::exact_name("c++utilities").page_url(1),
+            "https://aur.archlinux.org/packages?PP=250&SeB=N&K=c%2B%2Butilities&SB=p&SO=d&O=0"
+        );
+        assert!(!popular.is_filtered());
+        assert!(newest_git.is_filtered());
+        assert!(orphans.is_filtered());
+    }
+
+    #[test]
+    fn treat_every_search_but_default_one_as_filtered() {
+        for search_by in ListSearchBy::value_variants() {
+            // Arrange
+            let without_keywords = PackagesListQuery {
+                search_by: search_by.as_param(),
+                ..PackagesListQuery::default()
+            };
+            let with_keywords = PackagesListQuery {
+                keywords: "qt5".into(),
+                ..without_keywords
+            };
+
+            // Act & Assert
+            assert_eq!(
+                without_keywords.is_filtered(),
+                *search_by != ListSearchBy::NameDesc,
+                "SeB={}",
+                search_by.as_param()
+            );
+            assert!(with_keywords.is_filtered(), "SeB={}", search_by.as_param());
+        }
+    }
+
+    #[test]
+    fn stop_at_last_page_of_packages_list() {
+        // Arrange
+        let page = |len: usize, pages_count: Option<u32>| PackagesListPage {
+            packages: vec![create_package_data().basic; len],
+            packages_count: None,
+            pages_count,
+        };
+
+        // Act & Assert
+        assert_eq!(page(250, Some(386)).last_page(1, None), Some(386));
+        assert_eq!(page(250, Some(386)).last_page(1, Some(10)), Some(10));
+        assert_eq!(page(250, None).last_page(1, None), None);
+        assert_eq!(page(143, Some(386)).last_page(386, Some(400)), Some(386));
+        assert_eq!(page(0, None).last_page(387, None), Some(387));
+    }
 }
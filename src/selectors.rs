@@ -10,15 +10,26 @@ lazy_static! {
     pub static ref TH_SELECTOR: Selector = Selector::parse("th").unwrap();
     pub static ref A_SELECTOR: Selector = Selector::parse("a").unwrap();
     pub static ref UL_DEPS_SELECTOR: Selector = Selector::parse("ul#pkgdepslist").unwrap();
+    pub static ref UL_REQS_SELECTOR: Selector = Selector::parse("ul#pkgreqslist").unwrap();
+    pub static ref A_ALL_REQS_SELECTOR: Selector =
+        Selector::parse("a[href*='all_reqs=1']").unwrap();
+    pub static ref UL_SRCS_SELECTOR: Selector = Selector::parse("ul#pkgsrcslist").unwrap();
+    pub static ref DIV_PKGBASE_PKGS_SELECTOR: Selector = Selector::parse("div#pkgs").unwrap();
+    pub static ref SPAN_FLAGGED_SELECTOR: Selector = Selector::parse("span.flagged").unwrap();
     pub static ref LI_SELECTOR: Selector = Selector::parse("li").unwrap();
     pub static ref EM_SELECTOR: Selector = Selector::parse("em").unwrap();
     pub static ref DIV_COMMENTS_SELECTOR: Selector = Selector::parse("div.comments").unwrap();
+    pub static ref H3_SELECTOR: Selector = Selector::parse("h3").unwrap();
     pub static ref H4_COMMENT_HEADER_SELECTOR: Selector =
         Selector::parse("h4.comment-header").unwrap();
+    pub static ref A_DATE_SELECTOR: Selector = Selector::parse("a.date").unwrap();
+    pub static ref SPAN_EDITED_SELECTOR: Selector = Selector::parse("span.edited").unwrap();
     pub static ref DIV_COMMENT_CONTENT_SELECTOR: Selector =
         Selector::parse("div.article-content").unwrap();
     pub static ref P_SELECTOR: Selector = Selector::parse("p").unwrap();
     pub static ref P_COMMENT_HEADER_NAV_SELECTOR: Selector =
         Selector::parse("p.comments-header-nav").unwrap();
     pub static ref A_PAGE_SELECTOR: Selector = Selector::parse("a.page").unwrap();
+    pub static ref DIV_PKGLIST_STATS_SELECTOR: Selector =
+        Selector::parse("div.pkglist-stats").unwrap();
 }
@@ -1,20 +1,28 @@
 use anyhow::Result;
 use bson::{doc, Bson, Document};
-use serde::Serialize;
 use tokio::{fs::File, io::AsyncWriteExt};
 
-use crate::models::PackageData;
+use crate::models::{parse_timestamp, PackageData};
 
-#[derive(Serialize)]
-struct Test {
-    name: String,
-    surname: String,
-}
+/// Names of the fields holding timestamps, which are stored in the native datetime types of
+/// the formats supporting them.
+pub const TIMESTAMP_FIELDS: [&str; 6] = [
+    "last_updated",
+    "first_submitted",
+    "out_of_date",
+    "posted_at",
+    "edited_at",
+    "removed_at",
+];
 
-pub fn serialize_to_bson(packages: Vec<PackageData>) -> Result<Vec<u8>> {
+pub fn serialize_to_bson(packages: &[PackageData]) -> Result<Vec<u8>> {
     let bson_vec: Vec<Bson> = packages
-        .into_iter()
-        .map(|pkg| bson::to_bson(&pkg).unwrap())
+        .iter()
+        .map(|pkg| {
+            let mut bson = bson::to_bson(pkg).unwrap();
+            timestamps_to_bson_datetimes(&mut bson);
+            bson
+        })
         .collect();
 
     let bson_doc = doc!("packages": bson_vec);
@@ -34,7 +42,165 @@ pub async fn save_to_binary_file(file_name: &str, bytes: &[u8]) -> Result<()> {
 pub fn read_binary_file_and_deserialize(path: &str) -> Result<Vec<PackageData>> {
     let file = std::fs::File::open(path)?;
     let document = Document::from_reader(file)?;
-    let pkgs = document.get("packages").unwrap().to_owned();
+    let mut pkgs = document.get("packages").unwrap().to_owned();
+    bson_datetimes_to_timestamps(&mut pkgs);
     let packages: Vec<PackageData> = bson::from_bson(pkgs)?;
     Ok(packages)
 }
+
+/// Reads packages from all BSON files of the pages ('page_<number>.bson') stored in the
+/// directory.
+pub fn read_pages_from_dir(dir: &str) -> Result<Vec<PackageData>> {
+    let mut packages = vec![];
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_page = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("page_") && name.ends_with(".bson"));
+
+        if is_page {
+            packages.extend(read_binary_file_and_deserialize(&path.to_string_lossy())?);
+        }
+    }
+
+    Ok(packages)
+}
+
+fn timestamps_to_bson_datetimes(bson: &mut Bson) {
+    match bson {
+        Bson::Document(doc) => {
+            for (key, value) in doc.iter_mut() {
+                match value {
+                    Bson::String(timestamp) if TIMESTAMP_FIELDS.contains(&key.as_str()) => {
+                        if let Ok(timestamp) = parse_timestamp(timestamp, "timestamp") {
+                            *value = Bson::DateTime(bson::DateTime::from_chrono(timestamp));
+                        }
+                    }
+                    _ => timestamps_to_bson_datetimes(value),
+                }
+            }
+        }
+        Bson::Array(array) => array.iter_mut().for_each(timestamps_to_bson_datetimes),
+        _ => {}
+    }
+}
+
+/// Converts datetimes back to the strings expected by the models. Files written before
+/// timestamps were typed hold strings already and are left untouched.
+fn bson_datetimes_to_timestamps(bson: &mut Bson) {
+    match bson {
+        Bson::DateTime(datetime) => {
+            *bson = Bson::String(datetime.to_chrono().to_rfc3339());
+        }
+        Bson::Document(doc) => doc
+            .iter_mut()
+            .for_each(|(_, v)| bson_datetimes_to_timestamps(v)),
+        Bson::Array(array) => array.iter_mut().for_each(bson_datetimes_to_timestamps),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_binary_file_and_deserialize, read_pages_from_dir, serialize_to_bson};
+    use crate::database::shared::create_package_data;
+    use anyhow::Result;
+    use bson::{Bson, Document};
+
+    #[test]
+    fn store_timestamps_as_bson_datetimes() -> Result<()> {
+        // Arrange
+        let pkg = create_package_data();
+        let path = std::env::temp_dir().join(format!("aur-raider-{}.bson", std::process::id()));
+
+        // Act
+        let bytes = serialize_to_bson(&[create_package_data()])?;
+        std::fs::write(&path, &bytes)?;
+        let read = read_binary_file_and_deserialize(path.to_str().unwrap())?;
+        std::fs::remove_file(&path)?;
+
+        // Assert
+        let document = Document::from_reader(bytes.as_slice())?;
+        let stored = &document.get_array("packages")?[0];
+        let last_updated = stored
+            .as_document()
+            .unwrap()
+            .get_document("basic")?
+            .get("last_updated");
+        assert!(matches!(last_updated, Some(Bson::DateTime(_))));
+
+        assert_eq!(read[0].basic.last_updated, pkg.basic.last_updated);
+        assert_eq!(read[0].comments[1].edited_at, pkg.comments[1].edited_at);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_timestamps_saved_as_aur_strings() -> Result<()> {
+        // Arrange
+        let mut document = bson::to_document(&create_package_data())?;
+        document
+            .get_document_mut("basic")?
+            .insert("last_updated", "2023-01-02 10:30 (UTC)");
+
+        // Act
+        let pkg: crate::models::PackageData = bson::from_document(document)?;
+
+        // Assert
+        assert_eq!(
+            pkg.basic.last_updated.to_rfc3339(),
+            "2023-01-02T10:30:00+00:00"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_lists_saved_as_joined_strings() -> Result<()> {
+        // Arrange
+        let mut document = bson::to_document(&create_package_data())?;
+        let additional = document.get_document_mut("additional")?;
+        additional.remove("conflicts");
+        additional.remove("licenses");
+        additional.insert("confilcts", "foo,bar>=2.0");
+        additional.insert("license", "GPL3");
+        additional.insert("keywords", Bson::Null);
+        additional.insert("provides", "java-runtime=17");
+
+        // Act
+        let pkg: crate::models::PackageData = bson::from_document(document)?;
+
+        // Assert
+        let additional = pkg.additional.unwrap();
+        assert_eq!(additional.conflicts.len(), 2);
+        assert_eq!(additional.conflicts[1].name, "bar");
+        assert_eq!(additional.conflicts[1].to_string(), "bar>=2.0");
+        assert_eq!(additional.licenses, vec!["GPL3"]);
+        assert!(additional.keywords.is_empty());
+        assert_eq!(additional.provides[0].name, "java-runtime");
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_all_pages_from_dir() -> Result<()> {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("aur-raider-pages-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let bytes = serialize_to_bson(&[create_package_data()])?;
+        std::fs::write(dir.join("page_1.bson"), &bytes)?;
+        std::fs::write(dir.join("page_2.bson"), &bytes)?;
+        std::fs::write(dir.join("checkpoint.json"), "{}")?;
+
+        // Act
+        let packages = read_pages_from_dir(dir.to_str().unwrap())?;
+        std::fs::remove_dir_all(&dir)?;
+
+        // Assert
+        assert_eq!(packages.len(), 2);
+
+        Ok(())
+    }
+}
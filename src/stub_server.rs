@@ -0,0 +1,140 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+    time::sleep,
+};
+
+/// Response returned by the [`StubServer`] for a single request.
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+}
+
+/// Local HTTP stand-in for the AUR which answers every request with the response built by the
+/// handler and records how many requests were served concurrently.
+pub struct StubServer {
+    pub addr: SocketAddr,
+    served: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+    handle: JoinHandle<()>,
+}
+
+impl StubServer {
+    pub async fn start<F>(delay: Duration, handler: F) -> Self
+    where
+        F: Fn(&str) -> StubResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler = Arc::new(handler);
+        let served = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let handle = {
+            let served = served.clone();
+            let max_in_flight = max_in_flight.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    let Ok((mut stream, _)) = listener.accept().await else {
+                        return;
+                    };
+                    let handler = handler.clone();
+                    let served = served.clone();
+                    let in_flight = in_flight.clone();
+                    let max_in_flight = max_in_flight.clone();
+
+                    tokio::spawn(async move {
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                        let path = read_request_path(&mut stream).await;
+                        sleep(delay).await;
+                        let response = handler(&path);
+
+                        let mut raw = format!(
+                            "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n",
+                            response.status,
+                            response.body.len()
+                        );
+                        for (name, value) in &response.headers {
+                            raw.push_str(&format!("{}: {}\r\n", name, value));
+                        }
+                        raw.push_str("\r\n");
+                        raw.push_str(&response.body);
+
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        served.fetch_add(1, Ordering::SeqCst);
+                        let _ = stream.write_all(raw.as_bytes()).await;
+                        let _ = stream.shutdown().await;
+                    });
+                }
+            })
+        };
+
+        Self {
+            addr,
+            served,
+            max_in_flight,
+            handle,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn served(&self) -> usize {
+        self.served.load(Ordering::SeqCst)
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn read_request_path(stream: &mut tokio::net::TcpStream) -> String {
+    let mut request = vec![];
+    let mut buff = [0; 1024];
+
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buff).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buff[..n]),
+        }
+    }
+
+    String::from_utf8_lossy(&request)
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string()
+}